@@ -0,0 +1,1356 @@
+use log::{debug, error, info, warn};
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::AUTHORIZATION;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{self, config, GuestTagSource, RetryConfig};
+use crate::connectivity::Connectivity;
+use crate::metrics::Metrics;
+use crate::roster::Roster;
+use crate::AppError;
+
+// Define the POST request payload for the get_by_slug endpoint
+#[derive(Serialize)]
+pub struct PostPayload {
+    pub access_token: String,
+    pub slug: String,
+}
+
+// Define the POST request payload for the guests endpoint
+#[derive(Serialize)]
+pub struct GuestsPostPayload {
+    pub access_token: String,
+    pub guest_tag: String,
+}
+
+// Define the POST request payload for the load_score endpoint
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoadScorePostPayload {
+    pub access_token: String,
+    pub checkpoint_id: i32,
+    pub guest_tag: String,
+    pub score: String,
+    // Sent as the Idempotency-Key header rather than in the body. Kept with queued
+    // entries so every resend of one submission carries the same key; entries queued
+    // before keys existed have none.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub idempotency_key: String,
+    // Reported to on_success_hook once a queued entry is accepted; never sent to the
+    // API. Empty for entries queued before they were kept.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub guest_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub checkpoint_name: String,
+}
+
+// Key identifying one submission of a score for one guest, so the server can tell a
+// retry of a request it already applied from a new score
+pub fn idempotency_key(submission_id: &str, guest_tag: &str) -> String {
+    format!("{}-{}", submission_id, guest_tag)
+}
+
+// Fresh id for one operator submission (a single guest or a whole team)
+pub fn new_submission_id() -> String {
+    format!("{:032x}", fastrand::u128(..))
+}
+
+// Manual Debug so the access token can never end up in a log line
+impl std::fmt::Debug for LoadScorePostPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadScorePostPayload")
+            .field("checkpoint_id", &self.checkpoint_id)
+            .field("guest_tag", &self.guest_tag)
+            .field("score", &self.score)
+            .field("idempotency_key", &self.idempotency_key)
+            .finish_non_exhaustive()
+    }
+}
+
+// Define the expected POST response structure for the get_by_slug endpoint.
+// Only `id` is required: missing fields fall back to their defaults (a score of 0 is
+// then refused by checkpoint_score) and keys we do not know yet are kept in `extra`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Checkpoint {
+    #[serde(default)]
+    pub event_id: i32,
+    pub id: i32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub repetible: i32,
+    #[serde(default)]
+    pub score: i32,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostResponse {
+    pub checkpoint: Checkpoint,
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+}
+
+// Define the expected POST response structure for the guests endpoint
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Guest {
+    #[serde(default)]
+    pub name: String,
+    pub tag: Option<String>, // Explicitly map the tag field
+    #[serde(flatten)]
+    pub other: serde_json::Value,
+}
+
+// Accepts both the flat {"guests": [...]} shape and the nested
+// {"data": {"guests": [...]}} one some API versions return
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(from = "GuestsWire")]
+pub struct GuestsPostResponse {
+    pub guests: Vec<Guest>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GuestsWire {
+    Flat { guests: Vec<Guest> },
+    Nested { data: GuestsData },
+}
+
+#[derive(Deserialize)]
+struct GuestsData {
+    guests: Vec<Guest>,
+}
+
+impl From<GuestsWire> for GuestsPostResponse {
+    fn from(wire: GuestsWire) -> Self {
+        let guests = match wire {
+            GuestsWire::Flat { guests } => guests,
+            GuestsWire::Nested { data } => data.guests,
+        };
+        GuestsPostResponse { guests }
+    }
+}
+
+// Define the expected POST response structure for the load_score endpoint.
+// Every field is optional on the wire: a 200 without "success" counts as accepted,
+// and keys we do not know yet are kept in `extra`.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct LoadScorePostResponse {
+    pub success: bool,
+    pub total_score: Option<i32>,
+    pub message: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+}
+
+impl Default for LoadScorePostResponse {
+    fn default() -> Self {
+        LoadScorePostResponse {
+            success: true,
+            total_score: None,
+            message: None,
+            extra: serde_json::Value::Null,
+        }
+    }
+}
+
+// Event branding returned by checkpoints/visual/{event_id}. Keys map to the UI as:
+//   "title"         -> Theme.event_title (shown on the preintro screen)
+//   "primary_color" -> Theme.primary (screen and button background, "#RRGGBB")
+//   "idle_messages" -> Theme.idle_message (rotated on the idle screen, unless set in config)
+// Missing keys keep the built-in look; unknown keys are kept in `extra` and not used.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct VisualConfig {
+    pub title: Option<String>,
+    pub primary_color: Option<String>,
+    pub idle_messages: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: serde_json::Value,
+}
+
+// Message stored in the response when the server answers 409 CONFLICT
+const SCORE_ALREADY_LOADED: &str = "Score already loaded";
+
+impl LoadScorePostResponse {
+    // Response used when the guest is known to have scored already (409 or the local check)
+    pub fn already_loaded_response() -> Self {
+        LoadScorePostResponse {
+            message: Some(SCORE_ALREADY_LOADED.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // True when the guest already had a score for this checkpoint
+    pub fn already_loaded(&self) -> bool {
+        self.message.as_deref() == Some(SCORE_ALREADY_LOADED)
+    }
+}
+
+// Input that failed validation, so the UI can point at the offending field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    AccessToken,
+    Slug,
+    GuestTags,
+    Score,
+}
+
+// Why a field was rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    Empty,
+    NoneGiven,
+    NotHex,
+    NotPrintable,
+    InvalidLength,
+    NotInteger,
+}
+
+// One validation failure. `value` is the rejected input where it helps the message
+// (guest tags); it is left empty for fields that must not be echoed, like the token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: Field,
+    pub reason: Reason,
+    pub value: String,
+}
+
+impl ValidationError {
+    fn new(field: Field, reason: Reason) -> Self {
+        ValidationError {
+            field,
+            reason,
+            value: String::new(),
+        }
+    }
+
+    fn with_value(field: Field, reason: Reason, value: &str) -> Self {
+        ValidationError {
+            field,
+            reason,
+            value: value.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.field, self.reason) {
+            (Field::AccessToken, _) => write!(f, "Access token cannot be empty"),
+            (Field::Slug, _) => write!(f, "Slug cannot be empty"),
+            (Field::GuestTags, Reason::NoneGiven) => write!(f, "At least one guest tag is required"),
+            (Field::GuestTags, Reason::NotHex) => write!(f, "Guest tag is not a hex UID: {:?}", self.value),
+            (Field::GuestTags, Reason::NotPrintable) => write!(f, "Guest tag has control characters: {:?}", self.value),
+            (Field::GuestTags, Reason::InvalidLength) => write!(f, "Guest tag has an invalid length: {:?}", self.value),
+            (Field::GuestTags, _) => write!(f, "Guest tags cannot be empty"),
+            (Field::Score, Reason::NotInteger) => write!(f, "Score must be a valid integer"),
+            (Field::Score, _) => write!(f, "Score cannot be empty"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Validate every input of a submission, reporting all failures rather than the first
+pub fn validate_inputs(access_token: &str, slug: &str, guest_tags: &[String], score: &str) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    if access_token.is_empty() {
+        errors.push(ValidationError::new(Field::AccessToken, Reason::Empty));
+    }
+    if slug.is_empty() {
+        errors.push(ValidationError::new(Field::Slug, Reason::Empty));
+    }
+    if guest_tags.is_empty() {
+        errors.push(ValidationError::new(Field::GuestTags, Reason::NoneGiven));
+    }
+    errors.extend(guest_tags.iter().filter_map(|tag| validate_guest_tag(tag).err()));
+    errors.extend(validate_score(score).err());
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+// A score must be a non-empty integer; also used by the keypad to enable submit
+pub fn validate_score(score: &str) -> Result<(), ValidationError> {
+    if score.is_empty() {
+        return Err(ValidationError::new(Field::Score, Reason::Empty));
+    }
+    if score.parse::<i32>().is_err() {
+        return Err(ValidationError::with_value(Field::Score, Reason::NotInteger, score));
+    }
+    Ok(())
+}
+
+// Check a guest tag and return it trimmed. See validate_guest_tag_from.
+pub fn validate_guest_tag(tag: &str) -> Result<String, ValidationError> {
+    validate_guest_tag_from(tag, config().guest_tag_source)
+}
+
+// Longest NDEF guest tag accepted, in characters; badge ids and URIs are far shorter
+const NDEF_TAG_MAX_LEN: usize = 256;
+
+// A card UID must be hex making up a UID length accepted by the config; colon separators
+// (uid_format = "upper_colon"/"lower_colon") are accepted. With guest_tag_source = "ndef"
+// the tag may instead be the text or URI from the badge's NDEF record, which is free
+// form: anything printable up to NDEF_TAG_MAX_LEN characters.
+pub fn validate_guest_tag_from(tag: &str, source: GuestTagSource) -> Result<String, ValidationError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(ValidationError::new(Field::GuestTags, Reason::Empty));
+    }
+    let hex: String = tag.chars().filter(|c| *c != ':').collect();
+    let uid_problem = if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(Reason::NotHex)
+    } else if !hex.len().is_multiple_of(2) || !config().uid_length_ok(hex.len() / 2) {
+        Some(Reason::InvalidLength)
+    } else {
+        None
+    };
+    let Some(reason) = uid_problem else {
+        return Ok(tag.to_string());
+    };
+    if source != GuestTagSource::Ndef {
+        return Err(ValidationError::with_value(Field::GuestTags, reason, tag));
+    }
+    if tag.chars().any(char::is_control) {
+        return Err(ValidationError::with_value(Field::GuestTags, Reason::NotPrintable, tag));
+    }
+    if tag.chars().count() > NDEF_TAG_MAX_LEN {
+        return Err(ValidationError::with_value(Field::GuestTags, Reason::InvalidLength, tag));
+    }
+    Ok(tag.to_string())
+}
+
+// Backoff before retry `attempt` (1-based): base_delay doubled per attempt, capped at
+// max_delay. With jitter the delay is drawn from [delay/2, delay] so several kiosks
+// recovering from the same outage don't retry in lockstep.
+pub fn retry_delay(attempt: u32, cfg: &RetryConfig) -> Duration {
+    let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+    let delay = cfg.base_delay.saturating_mul(factor).min(cfg.max_delay);
+    if cfg.jitter {
+        let half = delay / 2;
+        half + Duration::from_millis(fastrand::u64(0..=half.as_millis() as u64))
+    } else {
+        delay
+    }
+}
+
+// Sleep the backoff delay before another attempt, unless it would end past the deadline
+// (`remaining` is the time left, None without a deadline)
+fn wait_before_retry(name: &str, attempt: u32, cfg: &RetryConfig, remaining: Option<Duration>) -> Result<(), AppError> {
+    let delay = retry_delay(attempt, cfg);
+    if remaining.is_some_and(|remaining| delay >= remaining) {
+        warn!("{} gave up: retrying would pass the {:?} deadline", name, cfg.deadline);
+        return Err(AppError::DeadlineExceeded(cfg.deadline));
+    }
+    thread::sleep(delay);
+    Ok(())
+}
+
+// Mask a credential for logging, keeping only its last 4 characters so operators can
+// tell tokens apart. Anything that might log a token must go through this.
+pub fn redact(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 4 {
+        return "*".repeat(chars.len());
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+// The one place the access token is attached to a request
+fn authorize(builder: RequestBuilder, access_token: &str) -> RequestBuilder {
+    builder.bearer_auth(access_token)
+}
+
+// Replace a top-level "access_token" in a JSON body; non-JSON bodies are logged as-is
+fn redact_body(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            if let Some(token) = value.get_mut("access_token") {
+                let masked = redact(token.as_str().unwrap_or_default());
+                *token = serde_json::Value::from(masked);
+            }
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+// Log method, URL, headers and body of an outgoing request with credentials redacted
+fn trace_request(name: &str, request: &Request) {
+    let headers: Vec<String> = request
+        .headers()
+        .iter()
+        .map(|(key, value)| {
+            if key == AUTHORIZATION {
+                let credential = value.to_str().unwrap_or_default();
+                let (scheme, token) = credential.split_once(' ').unwrap_or(("", credential));
+                format!("{}: {} {}", key, scheme, redact(token))
+            } else {
+                format!("{}: {}", key, value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect();
+    let body = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .map(|bytes| redact_body(&String::from_utf8_lossy(bytes)))
+        .unwrap_or_default();
+    info!("HTTP {} -> {} {} [{}] {}", name, request.method(), request.url(), headers.join(", "), body);
+}
+
+// Send a request, retrying transport errors and cfg.retry_statuses answers with backoff.
+// Gives up with DeadlineExceeded once cfg.deadline has passed, or when the next wait
+// would end past it, even if attempts are left.
+// `build` is called for every attempt because a sent RequestBuilder cannot be reused.
+// A 200 body is deserialized into T, 401 becomes TokenExpired and any other status an ApiError,
+// as does a body over max_response_kb.
+// With trace_http every attempt is logged: request, status, elapsed time and body.
+fn send_with_retry<T: DeserializeOwned>(
+    name: &str,
+    build: impl Fn() -> RequestBuilder,
+    max_retries: u32,
+    cfg: &RetryConfig,
+    metrics: &Metrics,
+) -> Result<T, AppError> {
+    let trace = config().trace_http;
+    let deadline = (!cfg.deadline.is_zero()).then(|| Instant::now() + cfg.deadline);
+    // Time left before the deadline, or None when there is no deadline
+    let remaining = || deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+    for attempt in 1..=max_retries {
+        let mut builder = build();
+        if let Some(remaining) = remaining() {
+            if remaining.is_zero() {
+                return Err(AppError::DeadlineExceeded(cfg.deadline));
+            }
+            // The last attempt may only use what is left of the deadline
+            builder = builder.timeout(remaining.min(config().request_timeout));
+        }
+        let (client, request) = builder.build_split();
+        let request = request?;
+        if trace {
+            trace_request(name, &request);
+        }
+        let started = Instant::now();
+        let result = client.execute(request);
+        metrics.record_request(started.elapsed());
+        match result {
+            Ok(resp) => {
+                let status = resp.status();
+                let body = read_body(resp, config().max_response_bytes);
+                if trace {
+                    let shown = body.as_deref().map(redact_body).unwrap_or_else(|e| format!("<unreadable: {}>", e));
+                    info!("HTTP {} <- {} in {:?} {}", name, status, started.elapsed(), shown);
+                }
+                match status {
+                    StatusCode::OK => {
+                        let text = body.map_err(|message| {
+                            error!("{} response rejected: {}", name, message);
+                            AppError::ApiError { status: status.as_u16(), message }
+                        })?;
+                        debug!("{} response: {}", name, text);
+                        return serde_json::from_str::<T>(&text).map_err(|e| {
+                            error!("{} deserialization error: {}", name, e);
+                            AppError::Json(e)
+                        });
+                    }
+                    StatusCode::UNAUTHORIZED => {
+                        return Err(AppError::TokenExpired);
+                    }
+                    status if attempt < max_retries && cfg.retry_statuses.contains(&status.as_u16()) => {
+                        debug!("{} answered {}, retrying (attempt {})", name, status, attempt);
+                        wait_before_retry(name, attempt, cfg, remaining())?;
+                        metrics.record_retry();
+                    }
+                    status => {
+                        let message = body.map(|body| error_snippet(&body)).unwrap_or_else(|message| message);
+                        return Err(AppError::ApiError {
+                            status: status.as_u16(),
+                            message,
+                        });
+                    }
+                }
+            }
+            Err(e) => {
+                if trace {
+                    info!("HTTP {} failed in {:?}: {}", name, started.elapsed(), e);
+                }
+                if e.is_timeout() && remaining().is_some_and(|remaining| remaining.is_zero()) {
+                    warn!("{} gave up after {:?}: {}", name, cfg.deadline, e);
+                    return Err(AppError::DeadlineExceeded(cfg.deadline));
+                }
+                if attempt == max_retries {
+                    return Err(AppError::from(e));
+                }
+                debug!("{} failed: {}, retrying (attempt {})", name, e, attempt);
+                wait_before_retry(name, attempt, cfg, remaining())?;
+                metrics.record_retry();
+            }
+        }
+    }
+    Err(AppError::ApiError {
+        status: 0,
+        message: "Max retries reached".to_string(),
+    })
+}
+
+// Read a response body, refusing to buffer more than `limit` bytes so a broken or hostile
+// endpoint cannot exhaust memory on the kiosk. Errors are messages for an ApiError.
+fn read_body(resp: Response, limit: u64) -> Result<String, String> {
+    if let Some(len) = resp.content_length()
+        && len > limit
+    {
+        return Err(format!("response of {} bytes exceeds max_response_kb", len));
+    }
+    // One byte over the limit tells a body of exactly `limit` bytes from a longer one
+    let mut bytes = Vec::new();
+    resp.take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("unreadable response body: {}", e))?;
+    if bytes.len() as u64 > limit {
+        return Err(format!("response larger than {} bytes exceeds max_response_kb", limit));
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// Longest error body kept in an ApiError message, in characters
+const ERROR_SNIPPET_LEN: usize = 200;
+
+// Short readable message from an error body. Proxies and load balancers answer with HTML
+// pages, which are reduced to their text; a JSON "message" or "error" field is used as is.
+fn error_snippet(body: &str) -> String {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(body)
+        && let Some(message) = ["message", "error"].iter().find_map(|key| value.get(key).and_then(|v| v.as_str()))
+    {
+        return message.to_string();
+    }
+    let mut text = String::with_capacity(body.len());
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return "Empty error body".to_string();
+    }
+    match text.char_indices().nth(ERROR_SNIPPET_LEN) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+// Function for the get_by_slug POST request with retry logic
+pub fn post_get_by_slug(
+    client: &Client,
+    access_token: &str,
+    slug: &str,
+    max_retries: u32,
+    metrics: &Metrics,
+) -> Result<PostResponse, AppError> {
+    let post_url = config().endpoint_url("checkpoints/get_by_slug");
+    let payload = PostPayload {
+        access_token: access_token.to_string(),
+        slug: slug.to_string(),
+    };
+
+    let result = send_with_retry(
+        "post_get_by_slug",
+        || {
+            client
+                .post(&post_url)
+                .header("Content-Type", "application/json")
+                .json(&payload)
+        },
+        max_retries,
+        &config().retry,
+        metrics,
+    );
+    checkpoint_result(result, slug)
+}
+
+// A 404 means the slug is wrong, not that the server is down
+pub fn checkpoint_result(result: Result<PostResponse, AppError>, slug: &str) -> Result<PostResponse, AppError> {
+    match result {
+        Err(AppError::ApiError { status, .. }) if status == StatusCode::NOT_FOUND.as_u16() => {
+            Err(AppError::CheckpointNotFound { slug: slug.to_string() })
+        }
+        result => result,
+    }
+}
+
+// Function for the visual GET request with retry logic
+pub fn get_visual(
+    client: &Client,
+    access_token: &str,
+    event_id: i32,
+    max_retries: u32,
+    metrics: &Metrics,
+) -> Result<VisualConfig, AppError> {
+    let get_url = config().endpoint_url(&format!("checkpoints/visual/{}", event_id));
+
+    send_with_retry(
+        "get_visual",
+        || authorize(client.get(&get_url), access_token),
+        max_retries,
+        &config().retry,
+        metrics,
+    )
+}
+
+// Function for the guests POST request with retry logic.
+// Returns GuestNotFound when the tag is not registered for the event.
+pub fn post_guests(
+    client: &Client,
+    access_token: &str,
+    guest_tag: &str,
+    max_retries: u32,
+    metrics: &Metrics,
+) -> Result<GuestsPostResponse, AppError> {
+    let post_url = config().endpoint_url("control/guests");
+    let payload = GuestsPostPayload {
+        access_token: access_token.to_string(),
+        guest_tag: guest_tag.to_string(),
+    };
+
+    let result = send_with_retry::<GuestsPostResponse>(
+        "post_guests",
+        || {
+            authorize(client.post(&post_url), access_token)
+                .header("Content-Type", "application/json")
+                .json(&payload)
+        },
+        max_retries,
+        &config().retry,
+        metrics,
+    );
+    guests_result(result)
+}
+
+// Unregistered cards come back either as 404 or as an empty guests list
+pub fn guests_result(result: Result<GuestsPostResponse, AppError>) -> Result<GuestsPostResponse, AppError> {
+    match result {
+        Err(AppError::ApiError { status, .. }) if status == StatusCode::NOT_FOUND.as_u16() => Err(AppError::GuestNotFound),
+        Ok(resp) if resp.guests.is_empty() => Err(AppError::GuestNotFound),
+        result => result,
+    }
+}
+
+// Function for the load_score POST request with retry logic.
+// A 409 CONFLICT means the guest already has a score here and is reported as success.
+pub fn post_load_score(
+    client: &Client,
+    submission: &LoadScorePostPayload,
+    max_retries: u32,
+    metrics: &Metrics,
+) -> Result<LoadScorePostResponse, AppError> {
+    let post_url = config().endpoint_url("checkpoints/load_score");
+    let access_token = &submission.access_token;
+    let idempotency_key = &submission.idempotency_key;
+    // The key travels as a header only, and the names stay on the kiosk
+    let payload = LoadScorePostPayload {
+        idempotency_key: String::new(),
+        guest_name: String::new(),
+        checkpoint_name: String::new(),
+        ..submission.clone()
+    };
+
+    let result = send_with_retry::<LoadScorePostResponse>(
+        "post_load_score",
+        || {
+            authorize(client.post(&post_url), access_token)
+                .header("Content-Type", "application/json")
+                .header("Idempotency-Key", idempotency_key)
+                .json(&payload)
+        },
+        max_retries,
+        &config().retry,
+        metrics,
+    );
+    load_score_result(result)
+}
+
+// A 409 CONFLICT is the guest's earlier score, reported as success
+pub fn load_score_result(result: Result<LoadScorePostResponse, AppError>) -> Result<LoadScorePostResponse, AppError> {
+    match result {
+        Err(AppError::ApiError { status, .. }) if status == StatusCode::CONFLICT.as_u16() => {
+            debug!("post_load_score response (CONFLICT): {}", SCORE_ALREADY_LOADED);
+            Ok(LoadScorePostResponse::already_loaded_response())
+        }
+        // A 200 that explicitly refuses the score is a rejection, not a success. It is
+        // reported as 422 so callers (and the queue drainer) treat it like any other 4xx.
+        Ok(resp) if !resp.success => Err(AppError::ApiError {
+            status: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+            message: resp.message.unwrap_or_else(|| "Score rejected".to_string()),
+        }),
+        result => result,
+    }
+}
+
+// Fetch every configured checkpoint and its event's visuals once at startup, so neither
+// the first scan nor any score submission waits on station metadata. Failures are only
+// logged; those lookups are retried on first use.
+pub fn prewarm(api: &dyn ApiClient) {
+    let started = Instant::now();
+    let slugs: std::collections::BTreeSet<String> = config::checkpoint_slugs().into_values().collect();
+    let mut events = std::collections::BTreeSet::new();
+    for slug in &slugs {
+        match api.get_checkpoint(slug) {
+            Ok(resp) => {
+                events.insert(resp.checkpoint.event_id);
+            }
+            Err(e) => warn!("Prewarm: checkpoint {} not loaded: {}", slug, e),
+        }
+    }
+    for event_id in events {
+        if let Err(e) = api.get_visual(event_id) {
+            warn!("Prewarm: visuals for event {} not loaded: {}", event_id, e);
+        }
+    }
+    info!("Station metadata prewarmed in {:?}", started.elapsed());
+}
+
+// Build the shared HTTP client; the timeout bounds each attempt so a stalled
+// connection becomes a retriable error instead of hanging the NFC thread
+pub fn build_client() -> Result<Client, AppError> {
+    let mut builder = Client::builder().timeout(config().request_timeout);
+    // Keep connections open between scans so each lookup skips the TLS handshake.
+    // HTTP/2 is negotiated through ALPN when the server offers it.
+    if config().pool_idle_timeout.is_zero() {
+        builder = builder.pool_max_idle_per_host(0);
+    } else {
+        builder = builder.pool_idle_timeout(config().pool_idle_timeout);
+    }
+    if !config().tcp_keepalive.is_zero() {
+        builder = builder.tcp_keepalive(config().tcp_keepalive);
+    }
+    // Venues behind SSL inspection need their proxy's CA trusted on top of the system store
+    if let Some(path) = &config().ca_cert_path {
+        let pem = std::fs::read(path)
+            .map_err(|e| AppError::Config(format!("Failed to read CA file {}: {}", path.display(), e)))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| AppError::Config(format!("Invalid CA file {}: {}", path.display(), e)))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    if config().danger_accept_invalid_certs {
+        warn!("danger_accept_invalid_certs is set: TLS certificates are NOT verified");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
+}
+
+
+// API operations used by the UI, NFC and queue code. Callers depend on this
+// trait rather than on reqwest so the backend can be swapped out.
+pub trait ApiClient: Send + Sync {
+    fn get_checkpoint(&self, slug: &str) -> Result<PostResponse, AppError>;
+    fn get_guests(&self, guest_tag: &str) -> Result<GuestsPostResponse, AppError>;
+    // Every attempt for one submission, including later resends from the queue,
+    // must use the same idempotency_key
+    fn load_score(
+        &self,
+        checkpoint_id: i32,
+        guest_tag: &str,
+        score: &str,
+        idempotency_key: &str,
+    ) -> Result<LoadScorePostResponse, AppError>;
+    // Submit several scores, one result per entry in order. Sent one by one over the
+    // shared connection until the API offers a batch endpoint, which only needs to
+    // replace this method. After a failure worth queueing the rest are not tried and
+    // come back as NotSent, so an outage costs one request's retries, not one per entry.
+    fn load_scores(&self, batch: &[LoadScorePostPayload]) -> Vec<Result<LoadScorePostResponse, AppError>> {
+        let mut results = Vec::with_capacity(batch.len());
+        for submission in batch {
+            if results.last().is_some_and(|result: &Result<_, _>| result.as_ref().is_err_and(crate::is_queueable)) {
+                results.push(Err(AppError::NotSent));
+                continue;
+            }
+            results.push(self.load_score(submission.checkpoint_id, &submission.guest_tag, &submission.score, &submission.idempotency_key));
+        }
+        results
+    }
+    fn get_visual(&self, event_id: i32) -> Result<VisualConfig, AppError>;
+    // One cheap uncached request to check the API and the token are still good
+    fn ping(&self) -> Result<(), AppError>;
+    // Swap in a fresh access token, e.g. after the old one expired
+    fn set_access_token(&self, access_token: String);
+}
+
+// ApiClient backed by the blocking reqwest client and the retrying request functions
+pub struct HttpApi {
+    client: Client,
+    access_token: RwLock<String>,
+    max_retries: u32,
+    connectivity: Arc<Connectivity>,
+    metrics: Arc<Metrics>,
+    roster: Option<Arc<Roster>>,
+    // Station metadata only changes between events, so each slug and event is fetched
+    // once per run; see prewarm
+    checkpoints: RwLock<HashMap<String, PostResponse>>,
+    visuals: RwLock<HashMap<i32, VisualConfig>>,
+}
+
+impl HttpApi {
+    pub fn new(access_token: String, connectivity: Arc<Connectivity>, metrics: Arc<Metrics>) -> Result<HttpApi, AppError> {
+        Ok(HttpApi {
+            client: build_client()?,
+            access_token: RwLock::new(access_token),
+            max_retries: config().retry.max_retries,
+            connectivity,
+            metrics,
+            roster: None,
+            checkpoints: RwLock::new(HashMap::new()),
+            visuals: RwLock::new(HashMap::new()),
+        })
+    }
+
+    // Resolve guests from the local roster when the network is down, and keep it
+    // current with every successful online lookup
+    pub fn with_roster(mut self, roster: Arc<Roster>) -> HttpApi {
+        self.roster = Some(roster);
+        self
+    }
+
+    fn token(&self) -> String {
+        self.access_token.read().unwrap().clone()
+    }
+}
+
+impl ApiClient for HttpApi {
+    fn get_checkpoint(&self, slug: &str) -> Result<PostResponse, AppError> {
+        if let Some(cached) = self.checkpoints.read().unwrap().get(slug) {
+            return Ok(cached.clone());
+        }
+        let result = post_get_by_slug(&self.client, &self.token(), slug, self.max_retries, &self.metrics);
+        self.connectivity.record(&result);
+        if let Ok(resp) = &result {
+            self.checkpoints.write().unwrap().insert(slug.to_string(), resp.clone());
+        }
+        result
+    }
+
+    fn get_guests(&self, guest_tag: &str) -> Result<GuestsPostResponse, AppError> {
+        let result = post_guests(&self.client, &self.token(), guest_tag, self.max_retries, &self.metrics);
+        self.connectivity.record(&result);
+        let Some(roster) = &self.roster else {
+            return result;
+        };
+        match result {
+            Ok(resp) => {
+                if let Err(e) = roster.upsert(&resp.guests) {
+                    warn!("Failed to update guest roster: {}", e);
+                }
+                Ok(resp)
+            }
+            Err(e @ (AppError::Http(_) | AppError::DeadlineExceeded(_))) => match roster.resolve_guest_local(guest_tag) {
+                Ok(Some(guest)) => {
+                    info!("Offline, resolved {} from the local roster", guest_tag);
+                    Ok(GuestsPostResponse { guests: vec![guest] })
+                }
+                Ok(None) => Err(e),
+                Err(roster_err) => {
+                    warn!("Local roster lookup failed: {}", roster_err);
+                    Err(e)
+                }
+            },
+            result => result,
+        }
+    }
+
+    fn load_score(
+        &self,
+        checkpoint_id: i32,
+        guest_tag: &str,
+        score: &str,
+        idempotency_key: &str,
+    ) -> Result<LoadScorePostResponse, AppError> {
+        let submission = LoadScorePostPayload {
+            access_token: self.token(),
+            checkpoint_id,
+            guest_tag: guest_tag.to_string(),
+            score: score.to_string(),
+            idempotency_key: idempotency_key.to_string(),
+            guest_name: String::new(),
+            checkpoint_name: String::new(),
+        };
+        let result = post_load_score(&self.client, &submission, self.max_retries, &self.metrics);
+        self.connectivity.record(&result);
+        result
+    }
+
+    fn get_visual(&self, event_id: i32) -> Result<VisualConfig, AppError> {
+        // A checkpoint that came back without event_id defaults it to 0
+        if event_id == 0 {
+            return Err(AppError::MissingEventId);
+        }
+        if let Some(cached) = self.visuals.read().unwrap().get(&event_id) {
+            return Ok(cached.clone());
+        }
+        let result = get_visual(&self.client, &self.token(), event_id, self.max_retries, &self.metrics);
+        if let Ok(visual) = &result {
+            self.visuals.write().unwrap().insert(event_id, visual.clone());
+        }
+        result
+    }
+
+    // A single get_by_slug attempt for the event's checkpoint, bypassing the cache
+    fn ping(&self) -> Result<(), AppError> {
+        let Some(slug) = config().event_slug() else {
+            return Ok(());
+        };
+        let result = post_get_by_slug(&self.client, &self.token(), slug, 1, &self.metrics);
+        self.connectivity.record(&result);
+        result.map(|_| ())
+    }
+
+    fn set_access_token(&self, access_token: String) {
+        *self.access_token.write().unwrap() = access_token;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockApi, Reply};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Answer one request per connection with `replies` in order on a local port.
+    // Returns the URL to post to and the number of requests answered so far.
+    fn serve(replies: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let answered = Arc::new(AtomicUsize::new(0));
+        let counter = answered.clone();
+        thread::spawn(move || {
+            for (status, body) in replies {
+                let Ok((stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                let _ = reader.read_exact(&mut request_body);
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut stream = reader.into_inner();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        (url, answered)
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: false,
+            deadline: Duration::ZERO,
+            retry_statuses: vec![429, 503],
+        }
+    }
+
+    fn send(url: &str, cfg: &RetryConfig, metrics: &Metrics) -> Result<serde_json::Value, AppError> {
+        let client = Client::new();
+        send_with_retry("test", || client.post(url).body("{}"), cfg.max_retries, cfg, metrics)
+    }
+
+    fn tag_reason(tag: &str) -> Option<Reason> {
+        validate_guest_tag(tag).err().map(|e| e.reason)
+    }
+
+    #[test]
+    fn guest_tag_empty_or_blank_is_rejected() {
+        assert_eq!(tag_reason(""), Some(Reason::Empty));
+        assert_eq!(tag_reason("   \t"), Some(Reason::Empty));
+    }
+
+    #[test]
+    fn guest_tag_must_be_hex() {
+        assert_eq!(tag_reason("04A1B2CZ"), Some(Reason::NotHex));
+        assert_eq!(tag_reason("04 A1 B2 C3"), Some(Reason::NotHex));
+        assert_eq!(tag_reason("0x04A1B2"), Some(Reason::NotHex));
+    }
+
+    #[test]
+    fn guest_tag_length_must_be_a_valid_uid_length() {
+        // Odd number of hex digits
+        assert_eq!(tag_reason("04A1B2C"), Some(Reason::InvalidLength));
+        // 5 and 11 bytes are not UID lengths
+        assert_eq!(tag_reason("04A1B2C3D4"), Some(Reason::InvalidLength));
+        assert_eq!(tag_reason("04A1B2C3D4E5F60718293A"), Some(Reason::InvalidLength));
+    }
+
+    #[test]
+    fn guest_tag_valid_uids_are_accepted_trimmed() {
+        assert_eq!(validate_guest_tag("04A1B2C3").unwrap(), "04A1B2C3");
+        assert_eq!(validate_guest_tag(" 04a1b2c3d4e5f6 ").unwrap(), "04a1b2c3d4e5f6");
+        assert_eq!(validate_guest_tag("04A1B2C3D4E5F6071829").unwrap(), "04A1B2C3D4E5F6071829");
+        assert_eq!(validate_guest_tag("04:A1:B2:C3").unwrap(), "04:A1:B2:C3");
+    }
+
+    #[test]
+    fn flat_and_nested_guests_shapes_parse_the_same() {
+        let flat: GuestsPostResponse =
+            serde_json::from_str(r#"{"guests": [{"name": "Ana", "tag": "04A1B2C3"}, {"name": "Luis", "tag": "04D4E5F6"}]}"#).unwrap();
+        let nested: GuestsPostResponse =
+            serde_json::from_str(r#"{"data": {"guests": [{"name": "Ana", "tag": "04A1B2C3"}, {"name": "Luis", "tag": "04D4E5F6"}]}}"#)
+                .unwrap();
+        let summary = |resp: &GuestsPostResponse| -> Vec<(String, Option<String>)> {
+            resp.guests.iter().map(|guest| (guest.name.clone(), guest.tag.clone())).collect()
+        };
+        assert_eq!(summary(&flat), summary(&nested));
+        assert_eq!(summary(&flat)[1], ("Luis".to_string(), Some("04D4E5F6".to_string())));
+    }
+
+    #[test]
+    fn guests_in_neither_shape_are_an_error() {
+        assert!(serde_json::from_str::<GuestsPostResponse>(r#"{"data": {"people": []}}"#).is_err());
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        let cfg = RetryConfig {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: false,
+            ..RetryConfig::default()
+        };
+        let delays: Vec<u128> = (1..=5).map(|attempt| retry_delay(attempt, &cfg).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000]);
+        // Shifts past the width of u32 saturate instead of overflowing
+        assert_eq!(retry_delay(64, &cfg), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retry_delay_jitter_stays_between_half_and_full_delay() {
+        let cfg = RetryConfig {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: true,
+            ..RetryConfig::default()
+        };
+        for _ in 0..200 {
+            let delay = retry_delay(3, &cfg);
+            assert!((Duration::from_millis(200)..=Duration::from_millis(400)).contains(&delay), "{:?}", delay);
+            let capped = retry_delay(10, &cfg);
+            assert!((Duration::from_millis(500)..=Duration::from_secs(1)).contains(&capped), "{:?}", capped);
+        }
+    }
+
+    #[test]
+    fn retry_statuses_are_retried_until_success() {
+        let (url, answered) = serve(vec![(503, ""), (429, ""), (200, r#"{"ok":true}"#)]);
+        let metrics = Metrics::default();
+        let value = send(&url, &fast_retry(), &metrics).unwrap();
+        assert_eq!(value["ok"], true);
+        assert_eq!(answered.load(Ordering::SeqCst), 3);
+        assert_eq!(metrics.snapshot().retries, 2);
+    }
+
+    #[test]
+    fn status_outside_retry_statuses_is_final() {
+        let (url, answered) = serve(vec![(500, r#"{"message":"boom"}"#), (200, "{}")]);
+        match send(&url, &fast_retry(), &Metrics::default()) {
+            Err(AppError::ApiError { status, message }) => {
+                assert_eq!(status, 500);
+                assert_eq!(message, "boom");
+            }
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn last_retriable_status_is_returned_when_attempts_run_out() {
+        let (url, answered) = serve(vec![(503, ""), (503, ""), (503, "")]);
+        match send(&url, &fast_retry(), &Metrics::default()) {
+            Err(AppError::ApiError { status, .. }) => assert_eq!(status, 503),
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+        assert_eq!(answered.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn unauthorized_is_token_expired_without_retry() {
+        let (url, answered) = serve(vec![(401, ""), (200, "{}")]);
+        assert!(matches!(send(&url, &fast_retry(), &Metrics::default()), Err(AppError::TokenExpired)));
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn backoff_past_the_deadline_gives_up() {
+        let (url, answered) = serve(vec![(503, ""), (200, "{}")]);
+        let cfg = RetryConfig {
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(1),
+            deadline: Duration::from_millis(100),
+            ..fast_retry()
+        };
+        let started = Instant::now();
+        match send(&url, &cfg, &Metrics::default()) {
+            Err(AppError::DeadlineExceeded(deadline)) => assert_eq!(deadline, cfg.deadline),
+            other => panic!("expected DeadlineExceeded, got {:?}", other),
+        }
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+        // Gave up instead of sleeping the 500ms backoff
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    fn submission(tag: &str) -> LoadScorePostPayload {
+        LoadScorePostPayload {
+            access_token: String::new(),
+            checkpoint_id: 7,
+            guest_tag: tag.to_string(),
+            score: "10".to_string(),
+            idempotency_key: idempotency_key("test", tag),
+            guest_name: String::new(),
+            checkpoint_name: String::new(),
+        }
+    }
+
+    #[test]
+    fn checkpoint_404_is_checkpoint_not_found() {
+        let api = MockApi::new().with_checkpoint("wrong-slug", Reply::Status(404));
+        match api.get_checkpoint("wrong-slug") {
+            Err(AppError::CheckpointNotFound { slug }) => assert_eq!(slug, "wrong-slug"),
+            other => panic!("expected CheckpointNotFound, got {:?}", other),
+        }
+        // Other failures keep their status
+        let api = MockApi::new().with_checkpoint("busy", Reply::Status(503));
+        assert!(matches!(api.get_checkpoint("busy"), Err(AppError::ApiError { status: 503, .. })));
+    }
+
+    #[test]
+    fn guests_404_is_guest_not_found() {
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Status(404));
+        assert!(matches!(api.get_guests("04A1B2C3"), Err(AppError::GuestNotFound)));
+    }
+
+    #[test]
+    fn empty_guests_list_is_guest_not_found() {
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Body(Vec::new()));
+        assert!(matches!(api.get_guests("04A1B2C3"), Err(AppError::GuestNotFound)));
+    }
+
+    #[test]
+    fn registered_guest_is_returned() {
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Body(vec![mock::guest("Ana", Some("04A1B2C3"))]));
+        let guests = api.get_guests("04A1B2C3").unwrap().guests;
+        assert_eq!(guests.len(), 1);
+        assert_eq!(guests[0].name, "Ana");
+    }
+
+    #[test]
+    fn load_score_409_is_already_loaded() {
+        let api = MockApi::new().with_scores("04A1B2C3", vec![Reply::Status(409)]);
+        let resp = api.load_score(7, "04A1B2C3", "10", "key").unwrap();
+        assert!(resp.success);
+        assert!(resp.already_loaded());
+    }
+
+    #[test]
+    fn load_score_refused_in_a_200_is_422() {
+        let refused = LoadScorePostResponse {
+            success: false,
+            message: Some("Checkpoint closed".to_string()),
+            ..Default::default()
+        };
+        let silent = LoadScorePostResponse {
+            success: false,
+            ..Default::default()
+        };
+        let api = MockApi::new().with_scores("04A1B2C3", vec![Reply::Body(refused), Reply::Body(silent)]);
+        match api.load_score(7, "04A1B2C3", "10", "key") {
+            Err(AppError::ApiError { status, message }) => {
+                assert_eq!(status, 422);
+                assert_eq!(message, "Checkpoint closed");
+            }
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+        match api.load_score(7, "04A1B2C3", "10", "key") {
+            Err(AppError::ApiError { status, message }) => {
+                assert_eq!(status, 422);
+                assert_eq!(message, "Score rejected");
+            }
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_scores_stops_after_a_failure_worth_retrying() {
+        let api = MockApi::new().with_scores("04000002", vec![Reply::Offline]);
+        let batch = [submission("04000001"), submission("04000002"), submission("04000003")];
+        let results = api.load_scores(&batch);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(AppError::DeadlineExceeded(_))));
+        assert!(matches!(results[2], Err(AppError::NotSent)));
+        // The entry after the outage was held back for the queue, not sent
+        assert_eq!(api.sent_tags(), ["04000001", "04000002"]);
+    }
+
+    #[test]
+    fn load_scores_continues_after_a_rejection() {
+        let api = MockApi::new().with_scores("04000002", vec![Reply::Status(422)]);
+        let batch = [submission("04000001"), submission("04000002"), submission("04000003")];
+        let results = api.load_scores(&batch);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(AppError::ApiError { status: 422, .. })));
+        assert!(results[2].is_ok());
+        assert_eq!(api.sent_tags().len(), 3);
+    }
+
+    #[test]
+    fn mock_checkpoint_round_trips() {
+        let resp = PostResponse {
+            checkpoint: mock::checkpoint(7, "trivia-1", 0),
+            extra: serde_json::Value::Null,
+        };
+        let api = MockApi::new().with_checkpoint("trivia-1", Reply::Body(resp));
+        assert_eq!(api.get_checkpoint("trivia-1").unwrap().checkpoint.id, 7);
+    }
+
+    const BAD_GATEWAY_PAGE: &str = "<html><head><title>502 Bad Gateway</title></head>\n<body><center><h1>502 Bad Gateway</h1></center>\n<hr><center>nginx</center></body></html>";
+
+    #[test]
+    fn html_error_page_becomes_a_readable_api_error() {
+        let (url, _) = serve(vec![(502, BAD_GATEWAY_PAGE)]);
+        match send(&url, &fast_retry(), &Metrics::default()) {
+            Err(AppError::ApiError { status, message }) => {
+                assert_eq!(status, 502);
+                assert_eq!(message, "502 Bad Gateway 502 Bad Gateway nginx");
+            }
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_snippet_prefers_json_messages_and_truncates_text() {
+        assert_eq!(error_snippet(r#"{"error": "Invalid slug"}"#), "Invalid slug");
+        assert_eq!(error_snippet("  "), "Empty error body");
+        let long = format!("<p>{}</p>", "x".repeat(ERROR_SNIPPET_LEN + 50));
+        let snippet = error_snippet(&long);
+        assert_eq!(snippet.chars().count(), ERROR_SNIPPET_LEN + 1);
+        assert!(snippet.ends_with('…'));
+    }
+
+    #[test]
+    fn load_score_response_fields_are_all_optional() {
+        let resp: LoadScorePostResponse = serde_json::from_str("{}").unwrap();
+        assert!(resp.success);
+        assert_eq!(resp.total_score, None);
+        assert_eq!(resp.message, None);
+
+        let resp: LoadScorePostResponse =
+            serde_json::from_str(r#"{"success": false, "message": "Closed", "total_score": 40, "rank": 3}"#).unwrap();
+        assert!(!resp.success);
+        assert_eq!(resp.total_score, Some(40));
+        assert_eq!(resp.message.as_deref(), Some("Closed"));
+        assert_eq!(resp.extra["rank"], 3);
+    }
+
+    #[test]
+    fn guests_tolerate_missing_and_unknown_fields() {
+        let resp: GuestsPostResponse =
+            serde_json::from_str(r#"{"guests": [{"tag": "04A1B2C3", "email": "ana@example.com"}, {"name": "Luis"}], "page": 1}"#)
+                .unwrap();
+        assert_eq!(resp.guests[0].name, "");
+        assert_eq!(resp.guests[0].other["email"], "ana@example.com");
+        assert_eq!(resp.guests[1].tag, None);
+
+        let nested: GuestsPostResponse =
+            serde_json::from_str(r#"{"data": {"guests": [{"name": "Ana", "tag": "04A1B2C3", "table": 4}], "total": 1}}"#).unwrap();
+        assert_eq!(nested.guests[0].other["table"], 4);
+    }
+
+    #[test]
+    fn checkpoint_only_needs_an_id() {
+        let resp: PostResponse = serde_json::from_str(r#"{"checkpoint": {"id": 62, "color": "red"}, "status": "ok"}"#).unwrap();
+        let checkpoint = &resp.checkpoint;
+        assert_eq!(checkpoint.id, 62);
+        assert_eq!((checkpoint.event_id, checkpoint.score, checkpoint.repetible), (0, 0, 0));
+        assert_eq!(checkpoint.name, "");
+        assert_eq!(checkpoint.extra["color"], "red");
+        assert_eq!(resp.extra["status"], "ok");
+
+        assert!(serde_json::from_str::<PostResponse>(r#"{"checkpoint": {"name": "Trivia"}}"#).is_err());
+        assert!(serde_json::from_str::<PostResponse>(r#"{"status": "ok"}"#).is_err());
+    }
+
+    #[test]
+    fn visual_config_tolerates_missing_and_unknown_fields() {
+        let visual: VisualConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!((visual.title, visual.primary_color, visual.idle_messages), (None, None, None));
+
+        let visual: VisualConfig =
+            serde_json::from_str(r##"{"title": "Expo", "primary_color": "#112233", "logo_url": "https://example.com/l.png"}"##).unwrap();
+        assert_eq!(visual.title.as_deref(), Some("Expo"));
+        assert_eq!(visual.primary_color.as_deref(), Some("#112233"));
+        assert_eq!(visual.extra["logo_url"], "https://example.com/l.png");
+    }
+
+    fn ndef_reason(tag: &str) -> Option<Reason> {
+        validate_guest_tag_from(tag, GuestTagSource::Ndef).err().map(|e| e.reason)
+    }
+
+    #[test]
+    fn ndef_guest_tags_have_their_own_rule() {
+        assert_eq!(validate_guest_tag_from(" BADGE-0017 ", GuestTagSource::Ndef).unwrap(), "BADGE-0017");
+        assert_eq!(
+            validate_guest_tag_from("https://wonderlab.events/b/17", GuestTagSource::Ndef).unwrap(),
+            "https://wonderlab.events/b/17"
+        );
+        // Numeric badge ids are not mistaken for UIDs of a bad length
+        assert_eq!(validate_guest_tag_from("12345", GuestTagSource::Ndef).unwrap(), "12345");
+        // Cards without an NDEF record fall back to their UID, which is still accepted
+        assert_eq!(validate_guest_tag_from("04A1B2C3", GuestTagSource::Ndef).unwrap(), "04A1B2C3");
+
+        assert_eq!(ndef_reason("  "), Some(Reason::Empty));
+        assert_eq!(ndef_reason("BADGE\u{7}17"), Some(Reason::NotPrintable));
+        assert_eq!(ndef_reason(&"x".repeat(NDEF_TAG_MAX_LEN + 1)), Some(Reason::InvalidLength));
+    }
+
+    #[test]
+    fn uid_sourced_tags_are_still_held_to_the_uid_rule() {
+        assert_eq!(tag_reason("BADGE-0017"), Some(Reason::NotHex));
+        assert_eq!(
+            validate_guest_tag_from("12345", GuestTagSource::Uid).err().map(|e| e.reason),
+            Some(Reason::InvalidLength)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+// Append-only JSON-lines record of every scan and score submission, successes included,
+// for reconciling with the server after an event. Separate from the offline queue.
+static AUDIT_LOG: OnceLock<Mutex<BufWriter<File>>> = OnceLock::new();
+
+// (checkpoint_id, guest_tag) pairs the server has accepted, rebuilt from the log at
+// startup so non-repeatable checkpoints can be checked without a network call. Pairs
+// still in the offline queue are added by the queue; see mark_pending.
+static SCORED: OnceLock<Mutex<HashSet<(i32, String)>>> = OnceLock::new();
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    kind: &'a str,
+    reader: Option<&'a str>,
+    uid: &'a str,
+    guest_name: Option<&'a str>,
+    checkpoint_id: Option<i32>,
+    score: Option<&'a str>,
+    outcome: &'a str,
+}
+
+// The subset of an entry needed to rebuild SCORED
+#[derive(Deserialize)]
+struct RecordedEntry {
+    kind: String,
+    uid: String,
+    checkpoint_id: Option<i32>,
+    outcome: String,
+}
+
+// Open (or create) the audit log; entries are dropped with a warning if this fails
+pub fn init(path: &Path) {
+    load_scored(path);
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => {
+            info!("Audit log: {}", path.display());
+            let _ = AUDIT_LOG.set(Mutex::new(BufWriter::new(file)));
+        }
+        Err(e) => warn!("Audit log disabled, cannot open {}: {}", path.display(), e),
+    }
+}
+
+// A card lookup on `reader` and what came of it
+pub fn scan(reader: &str, uid: &str, guest_name: Option<&str>, outcome: &str) {
+    write(AuditEntry {
+        timestamp: now(),
+        kind: "scan",
+        reader: Some(reader),
+        uid,
+        guest_name,
+        checkpoint_id: None,
+        score: None,
+        outcome,
+    });
+}
+
+// A load_score submission for `guest_tag` and what came of it
+pub fn score(guest_tag: &str, checkpoint_id: i32, score: &str, outcome: &str) {
+    if counts_as_scored(outcome) {
+        scored().lock().unwrap().insert((checkpoint_id, guest_tag.to_string()));
+    }
+    write(AuditEntry {
+        timestamp: now(),
+        kind: "score",
+        reader: None,
+        uid: guest_tag,
+        guest_name: None,
+        checkpoint_id: Some(checkpoint_id),
+        score: Some(score),
+        outcome,
+    });
+}
+
+// A score waiting in the offline queue counts as given, so a guest who taps again
+// while the kiosk is offline (or after a restart) is not queued a second time
+pub fn mark_pending(checkpoint_id: i32, guest_tag: &str) {
+    scored().lock().unwrap().insert((checkpoint_id, guest_tag.to_string()));
+}
+
+// True if this guest already has an accepted or queued score for the checkpoint on
+// this kiosk
+pub fn already_scored(checkpoint_id: i32, guest_tag: &str) -> bool {
+    scored().lock().unwrap().contains(&(checkpoint_id, guest_tag.to_string()))
+}
+
+fn scored() -> &'static Mutex<HashSet<(i32, String)>> {
+    SCORED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn counts_as_scored(outcome: &str) -> bool {
+    outcome == "ok" || outcome == "already_loaded"
+}
+
+// Seed SCORED from earlier runs; unreadable lines are skipped
+fn load_scored(path: &Path) {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return;
+    };
+    let mut scored = scored().lock().unwrap();
+    for entry in contents.lines().filter_map(|line| serde_json::from_str::<RecordedEntry>(line).ok()) {
+        if entry.kind == "score"
+            && counts_as_scored(&entry.outcome)
+            && let Some(checkpoint_id) = entry.checkpoint_id
+        {
+            scored.insert((checkpoint_id, entry.uid));
+        }
+    }
+    info!("Audit log: {} earlier scores loaded", scored.len());
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+// One line per entry, flushed immediately so a power cut loses at most the entry being written
+fn write(entry: AuditEntry) {
+    let Some(log) = AUDIT_LOG.get() else {
+        return;
+    };
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("Failed to serialize audit entry: {}", e);
+            return;
+        }
+    };
+    let mut writer = log.lock().unwrap();
+    if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+        warn!("Failed to write audit log: {}", e);
+    }
+}
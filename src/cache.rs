@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::api::Guest;
+
+// In-memory UID -> guest cache so repeated taps resolve without a network call.
+// Entries expire after `ttl`; when full, expired entries go first, then the oldest.
+pub struct GuestCache {
+    entries: HashMap<String, (Guest, Instant)>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl GuestCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        GuestCache {
+            entries: HashMap::new(),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&mut self, uid: &str) -> Option<Guest> {
+        match self.entries.get(uid) {
+            Some((guest, stored_at)) if stored_at.elapsed() < self.ttl => Some(guest.clone()),
+            Some(_) => {
+                self.entries.remove(uid);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&mut self, uid: String, guest: Guest) {
+        if !self.enabled() {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&uid) {
+            let ttl = self.ttl;
+            self.entries.retain(|_, (_, stored_at)| stored_at.elapsed() < ttl);
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&uid) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, stored_at))| *stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(uid, (guest, Instant::now()));
+    }
+}
@@ -0,0 +1,56 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// Command-line arguments. Overrides here beat config.toml and RPIUI_ACCESS_TOKEN; the
+// one-shot commands (--list-readers, --export, ...) run and exit without the UI.
+#[derive(Parser, Debug)]
+#[command(version, about = "NFC check-in and trivia scoring kiosk")]
+pub struct Cli {
+    /// Config file to load instead of config.toml next to the executable
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// API access token, instead of RPIUI_ACCESS_TOKEN or token.txt
+    #[arg(long, value_name = "TOKEN")]
+    pub token: Option<String>,
+
+    /// API root, instead of base_url from the config file
+    #[arg(long, value_name = "URL")]
+    pub base_url: Option<String>,
+
+    /// Only use readers whose name contains NAME, instead of reader_names
+    #[arg(long, value_name = "NAME")]
+    pub reader: Option<String>,
+
+    /// Read card UIDs (hex, one per line) from stdin instead of the NFC reader
+    #[arg(long)]
+    pub simulate: bool,
+
+    /// Log every API call with tokens masked, like trace_http = true
+    #[arg(long)]
+    pub trace_http: bool,
+
+    /// Start the local fleet-management endpoint on PORT
+    #[arg(long, value_name = "PORT")]
+    pub control_port: Option<u16>,
+
+    /// Print every reader PC/SC can see, then exit
+    #[arg(long)]
+    pub list_readers: bool,
+
+    /// Write the audit log and unsent scores to PATH as JSON lines, then exit
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// Seed the offline roster from a saved guests response, then exit
+    #[arg(long, value_name = "GUESTS_JSON")]
+    pub sync_roster: Option<PathBuf>,
+
+    /// Check reader and API access, then exit with 0 if both work
+    #[arg(long)]
+    pub selfcheck: bool,
+
+    /// Print the lock_pin_sha256 value for PIN, then exit
+    #[arg(long, value_name = "PIN")]
+    pub hash_pin: Option<String>,
+}
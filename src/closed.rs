@@ -0,0 +1,75 @@
+use chrono::NaiveTime;
+use log::{error, info};
+use slint::Weak;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::config;
+use crate::AppWindow;
+
+// How often the open_hours schedule is checked
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Station closed: cards still get an error beep but nothing is looked up or scored, and
+// the "station closed" screen covers the UI. Toggled from the settings screen, the
+// control endpoint's POST /closed, or the open_hours schedule.
+static CLOSED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_closed() -> bool {
+    CLOSED.load(Ordering::SeqCst)
+}
+
+// Open or close the station; `by` says who asked, for the log
+pub fn set_closed(ui_handle: &Weak<AppWindow>, closed: bool, by: &str) {
+    if CLOSED.swap(closed, Ordering::SeqCst) != closed {
+        info!("Station {} by {}", if closed { "closed" } else { "opened" }, by);
+    }
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            ui.set_station_closed(closed);
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+// "HH:MM-HH:MM" in local time. An end before the start wraps past midnight.
+pub fn parse_open_hours(hours: &str) -> Result<(NaiveTime, NaiveTime), String> {
+    let (open, close) = hours
+        .split_once('-')
+        .ok_or_else(|| format!("open_hours must look like \"09:00-18:00\", not {:?}", hours))?;
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| format!("open_hours time {:?}: {}", time.trim(), e))
+    };
+    Ok((parse(open)?, parse(close)?))
+}
+
+fn is_open_at(open: NaiveTime, close: NaiveTime, now: NaiveTime) -> bool {
+    if open <= close {
+        open <= now && now < close
+    } else {
+        now >= open || now < close
+    }
+}
+
+// Background thread that opens and closes the station on the open_hours schedule. It
+// only acts when the schedule changes, so a manual toggle holds until the next boundary.
+// Does nothing without open_hours.
+pub fn spawn_schedule(ui_handle: Weak<AppWindow>) {
+    let Some((open, close)) = config().open_hours.as_deref().and_then(|hours| parse_open_hours(hours).ok()) else {
+        return;
+    };
+    info!("Station open from {} to {}", open.format("%H:%M"), close.format("%H:%M"));
+
+    thread::spawn(move || {
+        let mut scheduled = None;
+        loop {
+            let is_open = is_open_at(open, close, chrono::Local::now().time());
+            if scheduled != Some(is_open) {
+                set_closed(&ui_handle, !is_open, "open_hours");
+                scheduled = Some(is_open);
+            }
+            thread::sleep(SCHEDULE_CHECK_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,1013 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::AppError;
+
+// Retry/backoff settings shared by every API request
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+    // Wall-clock cap on one request including all its retries; zero means no cap
+    pub deadline: Duration,
+    // HTTP statuses retried like a transport error; any other non-200 answer is final
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            deadline: Duration::from_secs(8),
+            retry_statuses: vec![429, 500, 502, 503, 504],
+        }
+    }
+}
+
+// How card UIDs are written, both on screen and as the guest_tag sent to the API
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UidFormat {
+    // 04A1B2C3
+    #[default]
+    UpperNoSep,
+    // 04a1b2c3
+    LowerNoSep,
+    // 04:A1:B2:C3
+    UpperColon,
+    // 04:a1:b2:c3
+    LowerColon,
+}
+
+// What is sent as the guest_tag for a scanned card
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestTagSource {
+    // The card's hardware UID, written per uid_format
+    #[default]
+    Uid,
+    // The first NDEF text or URI record, falling back to the UID when there is none
+    Ndef,
+}
+
+// One quick-score button on the manual score screen
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScorePreset {
+    pub label: String,
+    pub value: i32,
+}
+
+// How a checkpoint turns the score given at the kiosk into the one submitted; see rules.rs
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScoreRule {
+    // Always this many points, whatever was entered
+    Fixed { points: i32 },
+    // The entered score, plus `bonus` the first time a guest scores at the checkpoint
+    FirstTapBonus { bonus: i32 },
+}
+
+// Configuration struct for NFC
+#[derive(Debug)]
+pub struct Config {
+    pub scan_interval: Duration,
+    pub post_read_delay: Duration,
+    pub stabilize_delay: Duration,
+    pub stabilize_delay_max: Duration,
+    pub uid_read_retries: u32,
+    pub uid_retry_delay: Duration,
+    pub rf_reset_after_failures: u32,
+    // Reader settings sent as escape commands at startup; None leaves the reader's own
+    pub reader_card_beep: Option<bool>,
+    pub reader_auto_polling: Option<bool>,
+    pub reader_poll_interval_ms: Option<u32>,
+    pub reader_names: Vec<String>,
+    pub valid_uid_lengths: Vec<usize>,
+    pub accept_any_uid_length: bool,
+    pub uid_format: UidFormat,
+    pub guest_tag_source: GuestTagSource,
+    pub locale: String,
+    pub base_url: String,
+    pub checkpoint_slugs: HashMap<String, String>,
+    pub request_timeout: Duration,
+    // Largest API response body read, in bytes
+    pub max_response_bytes: u64,
+    pub pool_idle_timeout: Duration,
+    pub tcp_keepalive: Duration,
+    pub queue_path: PathBuf,
+    pub audit_log_path: PathBuf,
+    pub queue_retry_interval: Duration,
+    pub queue_batch_size: usize,
+    pub score_batch_window: Duration,
+    pub metrics_log_interval: Duration,
+    pub reader_feedback: bool,
+    pub sound_enabled: bool,
+    pub sound_success_path: Option<PathBuf>,
+    pub sound_error_path: Option<PathBuf>,
+    pub idle_timeout: Duration,
+    pub retry: RetryConfig,
+    pub guest_cache_ttl: Duration,
+    pub guest_cache_size: usize,
+    pub confirmation_timeout: Duration,
+    pub error_display_duration: Duration,
+    pub idle_image: Option<PathBuf>,
+    pub idle_messages: Vec<String>,
+    pub idle_message_interval: Duration,
+    pub reader_reconnect_delay: Duration,
+    pub reader_reconnect_max_delay: Duration,
+    pub pcsc_max_attempts: u32,
+    pub max_batch_size: usize,
+    pub recent_scans_size: usize,
+    pub lookup_workers: usize,
+    pub trace_http: bool,
+    pub scanner_max_restarts: u32,
+    pub write_stamp: bool,
+    pub stamp_block: u8,
+    pub rescan_cooldown: Duration,
+    pub removal_debounce: Duration,
+    pub toggle_mode: bool,
+    pub use_checkpoint_score: bool,
+    pub diagnostic_mode: bool,
+    pub score_presets: Vec<ScorePreset>,
+    // Checkpoint slug -> scoring rule; checkpoints not listed submit the score as given
+    pub score_rules: HashMap<String, ScoreRule>,
+    pub control_bind: String,
+    pub token_check_interval: Duration,
+    pub heartbeat_interval: Duration,
+    pub settings_pin: Option<String>,
+    pub lock_pin_sha256: Option<String>,
+    pub lock_timeout: Duration,
+    pub open_hours: Option<String>,
+    // Only set from --token, never from the file; see load_access_token
+    pub access_token: Option<String>,
+    pub station_path: PathBuf,
+    pub station_slugs: Vec<String>,
+    pub on_success_hook: Option<String>,
+    pub roster_path: Option<PathBuf>,
+    pub ca_cert_path: Option<PathBuf>,
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scan_interval: Duration::from_millis(500),
+            post_read_delay: Duration::ZERO,
+            stabilize_delay: Duration::from_millis(100),
+            stabilize_delay_max: Duration::from_millis(400),
+            uid_read_retries: 2,
+            uid_retry_delay: Duration::from_millis(50),
+            rf_reset_after_failures: 0,
+            reader_card_beep: None,
+            reader_auto_polling: None,
+            reader_poll_interval_ms: None,
+            reader_names: vec!["ACR122".to_string()],
+            valid_uid_lengths: vec![4, 7, 8, 10],
+            accept_any_uid_length: false,
+            uid_format: UidFormat::default(),
+            guest_tag_source: GuestTagSource::default(),
+            locale: "en".to_string(),
+            base_url: "https://wonderlab.events/controlacceso/v2/api".to_string(),
+            // No default: a guessed slug would file one trivia's scores under another
+            checkpoint_slugs: HashMap::new(),
+            request_timeout: Duration::from_secs(10),
+            max_response_bytes: 4096 * 1024,
+            pool_idle_timeout: Duration::from_secs(300),
+            tcp_keepalive: Duration::from_secs(30),
+            queue_path: PathBuf::from("score_queue.json"),
+            audit_log_path: PathBuf::from("audit.log"),
+            queue_retry_interval: Duration::from_secs(30),
+            queue_batch_size: 10,
+            score_batch_window: Duration::ZERO,
+            metrics_log_interval: Duration::from_secs(300),
+            reader_feedback: true,
+            sound_enabled: false,
+            sound_success_path: None,
+            sound_error_path: None,
+            idle_timeout: Duration::from_secs(60),
+            retry: RetryConfig::default(),
+            guest_cache_ttl: Duration::from_secs(300),
+            guest_cache_size: 500,
+            confirmation_timeout: Duration::from_secs(3),
+            error_display_duration: Duration::from_secs(8),
+            idle_image: None,
+            idle_messages: Vec::new(),
+            idle_message_interval: Duration::from_secs(5),
+            reader_reconnect_delay: Duration::from_secs(1),
+            reader_reconnect_max_delay: Duration::from_secs(30),
+            pcsc_max_attempts: 0,
+            max_batch_size: 20,
+            recent_scans_size: 10,
+            lookup_workers: 1,
+            trace_http: false,
+            scanner_max_restarts: 5,
+            write_stamp: false,
+            stamp_block: 4,
+            rescan_cooldown: Duration::from_secs(3),
+            removal_debounce: Duration::from_millis(500),
+            toggle_mode: false,
+            use_checkpoint_score: false,
+            diagnostic_mode: false,
+            score_presets: Vec::new(),
+            score_rules: HashMap::new(),
+            control_bind: "127.0.0.1".to_string(),
+            token_check_interval: Duration::from_secs(5),
+            heartbeat_interval: Duration::ZERO,
+            settings_pin: None,
+            lock_pin_sha256: None,
+            lock_timeout: Duration::from_secs(300),
+            open_hours: None,
+            access_token: None,
+            station_path: PathBuf::from("station.txt"),
+            station_slugs: Vec::new(),
+            on_success_hook: None,
+            roster_path: None,
+            ca_cert_path: None,
+            danger_accept_invalid_certs: false,
+        }
+    }
+}
+
+// Keys accepted in config.toml; anything absent keeps its default
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    scan_interval_ms: Option<u64>,
+    post_read_delay_ms: Option<u64>,
+    stabilize_delay_ms: Option<u64>,
+    stabilize_delay_max_ms: Option<u64>,
+    uid_read_retries: Option<u32>,
+    rf_reset_after_failures: Option<u32>,
+    reader_card_beep: Option<bool>,
+    reader_auto_polling: Option<bool>,
+    reader_poll_interval_ms: Option<u32>,
+    uid_retry_delay_ms: Option<u64>,
+    reader_name: Option<String>,
+    reader_names: Option<Vec<String>>,
+    valid_uid_lengths: Option<Vec<usize>>,
+    accept_any_uid_length: Option<bool>,
+    uid_format: Option<UidFormat>,
+    guest_tag_source: Option<GuestTagSource>,
+    locale: Option<String>,
+    base_url: Option<String>,
+    checkpoint_slugs: Option<HashMap<String, String>>,
+    // Superseded by checkpoint_slugs; still accepted so old files keep loading
+    checkpoint_map: Option<toml::Value>,
+    request_timeout_ms: Option<u64>,
+    max_response_kb: Option<u64>,
+    pool_idle_timeout_secs: Option<u64>,
+    tcp_keepalive_secs: Option<u64>,
+    queue_path: Option<PathBuf>,
+    audit_log_path: Option<PathBuf>,
+    queue_retry_interval_secs: Option<u64>,
+    queue_batch_size: Option<usize>,
+    score_batch_window_ms: Option<u64>,
+    metrics_log_interval_secs: Option<u64>,
+    reader_feedback: Option<bool>,
+    sound_enabled: Option<bool>,
+    sound_success_path: Option<PathBuf>,
+    sound_error_path: Option<PathBuf>,
+    idle_timeout_secs: Option<u64>,
+    max_retries: Option<u32>,
+    base_delay_ms: Option<u64>,
+    max_delay_ms: Option<u64>,
+    retry_jitter: Option<bool>,
+    scan_deadline_ms: Option<u64>,
+    retry_statuses: Option<Vec<u16>>,
+    guest_cache_ttl_secs: Option<u64>,
+    guest_cache_size: Option<usize>,
+    confirmation_secs: Option<u64>,
+    error_display_secs: Option<u64>,
+    idle_image: Option<PathBuf>,
+    idle_messages: Option<Vec<String>>,
+    idle_message_secs: Option<u64>,
+    reader_reconnect_delay_ms: Option<u64>,
+    reader_reconnect_max_delay_ms: Option<u64>,
+    pcsc_max_attempts: Option<u32>,
+    max_batch_size: Option<usize>,
+    recent_scans_size: Option<usize>,
+    lookup_workers: Option<usize>,
+    trace_http: Option<bool>,
+    scanner_max_restarts: Option<u32>,
+    write_stamp: Option<bool>,
+    stamp_block: Option<u8>,
+    rescan_cooldown_ms: Option<u64>,
+    removal_debounce_ms: Option<u64>,
+    toggle_mode: Option<bool>,
+    use_checkpoint_score: Option<bool>,
+    diagnostic_mode: Option<bool>,
+    score_presets: Option<Vec<ScorePreset>>,
+    score_rules: Option<HashMap<String, ScoreRule>>,
+    control_bind: Option<String>,
+    token_check_interval_secs: Option<u64>,
+    heartbeat_interval_secs: Option<u64>,
+    settings_pin: Option<String>,
+    lock_pin_sha256: Option<String>,
+    lock_timeout_secs: Option<u64>,
+    open_hours: Option<String>,
+    station_path: Option<PathBuf>,
+    station_slugs: Option<Vec<String>>,
+    on_success_hook: Option<String>,
+    roster_path: Option<PathBuf>,
+    ca_cert_path: Option<PathBuf>,
+    danger_accept_invalid_certs: Option<bool>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+// Slugs swapped at runtime through the control endpoint; they take precedence over checkpoint_slugs
+static SLUG_OVERRIDES: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+impl Config {
+    // Load config from a TOML file, falling back to defaults for missing keys or a missing file
+    pub fn load_from_path(path: &Path) -> Result<Config, AppError> {
+        let mut config = Config::default();
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No config file at {}, using defaults", path.display());
+                return Ok(config);
+            }
+            Err(e) => {
+                return Err(AppError::Config(format!("Failed to read {}: {}", path.display(), e)));
+            }
+        };
+
+        let file: ConfigFile = toml::from_str(&contents)
+            .map_err(|e| AppError::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+
+        if let Some(ms) = file.scan_interval_ms {
+            config.scan_interval = Duration::from_millis(ms);
+            info!("Config override: scan_interval_ms = {}", ms);
+        }
+        if let Some(ms) = file.post_read_delay_ms {
+            config.post_read_delay = Duration::from_millis(ms);
+            info!("Config override: post_read_delay_ms = {}", ms);
+        }
+        if let Some(ms) = file.stabilize_delay_ms {
+            config.stabilize_delay = Duration::from_millis(ms);
+            info!("Config override: stabilize_delay_ms = {}", ms);
+        }
+        if let Some(ms) = file.stabilize_delay_max_ms {
+            config.stabilize_delay_max = Duration::from_millis(ms);
+            info!("Config override: stabilize_delay_max_ms = {}", ms);
+        }
+        if let Some(failures) = file.rf_reset_after_failures {
+            config.rf_reset_after_failures = failures;
+            info!("Config override: rf_reset_after_failures = {}", failures);
+        }
+        if let Some(enabled) = file.reader_card_beep {
+            config.reader_card_beep = Some(enabled);
+            info!("Config override: reader_card_beep = {}", enabled);
+        }
+        if let Some(enabled) = file.reader_auto_polling {
+            config.reader_auto_polling = Some(enabled);
+            info!("Config override: reader_auto_polling = {}", enabled);
+        }
+        if let Some(ms) = file.reader_poll_interval_ms {
+            config.reader_poll_interval_ms = Some(ms);
+            info!("Config override: reader_poll_interval_ms = {}", ms);
+        }
+        if let Some(retries) = file.uid_read_retries {
+            config.uid_read_retries = retries;
+            info!("Config override: uid_read_retries = {}", retries);
+        }
+        if let Some(ms) = file.uid_retry_delay_ms {
+            config.uid_retry_delay = Duration::from_millis(ms);
+            info!("Config override: uid_retry_delay_ms = {}", ms);
+        }
+        if let Some(name) = file.reader_name {
+            info!("Config override: reader_name = {:?}", name);
+            config.reader_names = vec![name];
+        }
+        if let Some(names) = file.reader_names {
+            info!("Config override: reader_names = {:?}", names);
+            config.reader_names = names;
+        }
+        if let Some(lengths) = file.valid_uid_lengths {
+            info!("Config override: valid_uid_lengths = {:?}", lengths);
+            config.valid_uid_lengths = lengths;
+        }
+        if let Some(any) = file.accept_any_uid_length {
+            config.accept_any_uid_length = any;
+            info!("Config override: accept_any_uid_length = {}", any);
+        }
+        if let Some(format) = file.uid_format {
+            info!("Config override: uid_format = {:?}", format);
+            config.uid_format = format;
+        }
+        if let Some(source) = file.guest_tag_source {
+            info!("Config override: guest_tag_source = {:?}", source);
+            config.guest_tag_source = source;
+        }
+        if let Some(locale) = file.locale {
+            info!("Config override: locale = {:?}", locale);
+            config.locale = locale;
+        }
+        if let Some(url) = file.base_url {
+            info!("Config override: base_url = {:?}", url);
+            config.base_url = url;
+        }
+        if let Some(slugs) = file.checkpoint_slugs {
+            info!("Config override: checkpoint_slugs = {:?}", slugs);
+            config.checkpoint_slugs = slugs;
+        }
+        if file.checkpoint_map.is_some() {
+            warn!("checkpoint_map is no longer used; checkpoint ids now come from checkpoint_slugs");
+        }
+        if let Some(ms) = file.request_timeout_ms {
+            config.request_timeout = Duration::from_millis(ms);
+            info!("Config override: request_timeout_ms = {}", ms);
+        }
+        if let Some(kb) = file.max_response_kb {
+            config.max_response_bytes = kb.saturating_mul(1024);
+            info!("Config override: max_response_kb = {}", kb);
+        }
+        if let Some(secs) = file.pool_idle_timeout_secs {
+            config.pool_idle_timeout = Duration::from_secs(secs);
+            info!("Config override: pool_idle_timeout_secs = {}", secs);
+        }
+        if let Some(secs) = file.tcp_keepalive_secs {
+            config.tcp_keepalive = Duration::from_secs(secs);
+            info!("Config override: tcp_keepalive_secs = {}", secs);
+        }
+        if let Some(path) = file.queue_path {
+            info!("Config override: queue_path = {}", path.display());
+            config.queue_path = path;
+        }
+        if let Some(path) = file.audit_log_path {
+            info!("Config override: audit_log_path = {}", path.display());
+            config.audit_log_path = path;
+        }
+        if let Some(secs) = file.queue_retry_interval_secs {
+            config.queue_retry_interval = Duration::from_secs(secs);
+            info!("Config override: queue_retry_interval_secs = {}", secs);
+        }
+        if let Some(size) = file.queue_batch_size {
+            config.queue_batch_size = size;
+            info!("Config override: queue_batch_size = {}", size);
+        }
+        if let Some(ms) = file.score_batch_window_ms {
+            config.score_batch_window = Duration::from_millis(ms);
+            info!("Config override: score_batch_window_ms = {}", ms);
+        }
+        if let Some(secs) = file.metrics_log_interval_secs {
+            config.metrics_log_interval = Duration::from_secs(secs);
+            info!("Config override: metrics_log_interval_secs = {}", secs);
+        }
+        if let Some(enabled) = file.reader_feedback {
+            config.reader_feedback = enabled;
+            info!("Config override: reader_feedback = {}", enabled);
+        }
+        if let Some(enabled) = file.sound_enabled {
+            config.sound_enabled = enabled;
+            info!("Config override: sound_enabled = {}", enabled);
+        }
+        if let Some(path) = file.sound_success_path {
+            info!("Config override: sound_success_path = {}", path.display());
+            config.sound_success_path = Some(path);
+        }
+        if let Some(path) = file.sound_error_path {
+            info!("Config override: sound_error_path = {}", path.display());
+            config.sound_error_path = Some(path);
+        }
+        if let Some(secs) = file.idle_timeout_secs {
+            config.idle_timeout = Duration::from_secs(secs);
+            info!("Config override: idle_timeout_secs = {}", secs);
+        }
+        if let Some(retries) = file.max_retries {
+            config.retry.max_retries = retries;
+            info!("Config override: max_retries = {}", retries);
+        }
+        if let Some(ms) = file.base_delay_ms {
+            config.retry.base_delay = Duration::from_millis(ms);
+            info!("Config override: base_delay_ms = {}", ms);
+        }
+        if let Some(ms) = file.max_delay_ms {
+            config.retry.max_delay = Duration::from_millis(ms);
+            info!("Config override: max_delay_ms = {}", ms);
+        }
+        if let Some(jitter) = file.retry_jitter {
+            config.retry.jitter = jitter;
+            info!("Config override: retry_jitter = {}", jitter);
+        }
+        if let Some(ms) = file.scan_deadline_ms {
+            config.retry.deadline = Duration::from_millis(ms);
+            info!("Config override: scan_deadline_ms = {}", ms);
+        }
+        if let Some(statuses) = file.retry_statuses {
+            info!("Config override: retry_statuses = {:?}", statuses);
+            config.retry.retry_statuses = statuses;
+        }
+        if let Some(secs) = file.guest_cache_ttl_secs {
+            config.guest_cache_ttl = Duration::from_secs(secs);
+            info!("Config override: guest_cache_ttl_secs = {}", secs);
+        }
+        if let Some(size) = file.guest_cache_size {
+            config.guest_cache_size = size;
+            info!("Config override: guest_cache_size = {}", size);
+        }
+        if let Some(secs) = file.confirmation_secs {
+            config.confirmation_timeout = Duration::from_secs(secs);
+            info!("Config override: confirmation_secs = {}", secs);
+        }
+        if let Some(secs) = file.error_display_secs {
+            config.error_display_duration = Duration::from_secs(secs);
+            info!("Config override: error_display_secs = {}", secs);
+        }
+        if let Some(path) = file.idle_image {
+            info!("Config override: idle_image = {}", path.display());
+            config.idle_image = Some(path);
+        }
+        if let Some(messages) = file.idle_messages {
+            info!("Config override: idle_messages = {:?}", messages);
+            config.idle_messages = messages;
+        }
+        if let Some(secs) = file.idle_message_secs {
+            config.idle_message_interval = Duration::from_secs(secs.max(1));
+            info!("Config override: idle_message_secs = {}", secs);
+        }
+        if let Some(ms) = file.reader_reconnect_delay_ms {
+            config.reader_reconnect_delay = Duration::from_millis(ms);
+            info!("Config override: reader_reconnect_delay_ms = {}", ms);
+        }
+        if let Some(ms) = file.reader_reconnect_max_delay_ms {
+            config.reader_reconnect_max_delay = Duration::from_millis(ms);
+            info!("Config override: reader_reconnect_max_delay_ms = {}", ms);
+        }
+        if let Some(attempts) = file.pcsc_max_attempts {
+            config.pcsc_max_attempts = attempts;
+            info!("Config override: pcsc_max_attempts = {}", attempts);
+        }
+        if let Some(size) = file.max_batch_size {
+            config.max_batch_size = size;
+            info!("Config override: max_batch_size = {}", size);
+        }
+        if let Some(size) = file.recent_scans_size {
+            config.recent_scans_size = size;
+            info!("Config override: recent_scans_size = {}", size);
+        }
+        if let Some(workers) = file.lookup_workers {
+            config.lookup_workers = workers;
+            info!("Config override: lookup_workers = {}", workers);
+        }
+        if let Some(enabled) = file.trace_http {
+            config.trace_http = enabled;
+            info!("Config override: trace_http = {}", enabled);
+        }
+        if let Some(restarts) = file.scanner_max_restarts {
+            config.scanner_max_restarts = restarts;
+            info!("Config override: scanner_max_restarts = {}", restarts);
+        }
+        if let Some(enabled) = file.write_stamp {
+            config.write_stamp = enabled;
+            info!("Config override: write_stamp = {}", enabled);
+        }
+        if let Some(block) = file.stamp_block {
+            config.stamp_block = block;
+            info!("Config override: stamp_block = {}", block);
+        }
+        if let Some(ms) = file.rescan_cooldown_ms {
+            config.rescan_cooldown = Duration::from_millis(ms);
+            info!("Config override: rescan_cooldown_ms = {}", ms);
+        }
+        if let Some(ms) = file.removal_debounce_ms {
+            config.removal_debounce = Duration::from_millis(ms);
+            info!("Config override: removal_debounce_ms = {}", ms);
+        }
+        if let Some(toggle) = file.toggle_mode {
+            config.toggle_mode = toggle;
+            info!("Config override: toggle_mode = {}", toggle);
+        }
+        if let Some(one_tap) = file.use_checkpoint_score {
+            config.use_checkpoint_score = one_tap;
+            info!("Config override: use_checkpoint_score = {}", one_tap);
+        }
+        if let Some(diagnostic) = file.diagnostic_mode {
+            config.diagnostic_mode = diagnostic;
+            info!("Config override: diagnostic_mode = {}", diagnostic);
+        }
+        if let Some(presets) = file.score_presets {
+            info!("Config override: score_presets = {:?}", presets);
+            config.score_presets = presets;
+        }
+        if let Some(rules) = file.score_rules {
+            info!("Config override: score_rules = {:?}", rules);
+            config.score_rules = rules;
+        }
+        if let Some(bind) = file.control_bind {
+            info!("Config override: control_bind = {:?}", bind);
+            config.control_bind = bind;
+        }
+        if let Some(secs) = file.token_check_interval_secs {
+            config.token_check_interval = Duration::from_secs(secs);
+            info!("Config override: token_check_interval_secs = {}", secs);
+        }
+        if let Some(secs) = file.heartbeat_interval_secs {
+            config.heartbeat_interval = Duration::from_secs(secs);
+            info!("Config override: heartbeat_interval_secs = {}", secs);
+        }
+        if let Some(pin) = file.settings_pin.filter(|pin| !pin.is_empty()) {
+            info!("Config override: settings_pin = <set>");
+            config.settings_pin = Some(pin);
+        }
+        if let Some(hook) = file.on_success_hook.filter(|hook| !hook.trim().is_empty()) {
+            info!("Config override: on_success_hook = {}", hook);
+            config.on_success_hook = Some(hook);
+        }
+        if let Some(hash) = file.lock_pin_sha256.filter(|hash| !hash.is_empty()) {
+            info!("Config override: lock_pin_sha256 = <set>");
+            config.lock_pin_sha256 = Some(hash.to_ascii_lowercase());
+        }
+        if let Some(secs) = file.lock_timeout_secs {
+            config.lock_timeout = Duration::from_secs(secs);
+            info!("Config override: lock_timeout_secs = {}", secs);
+        }
+        if let Some(hours) = file.open_hours.filter(|hours| !hours.trim().is_empty()) {
+            info!("Config override: open_hours = {}", hours);
+            config.open_hours = Some(hours);
+        }
+        if let Some(path) = file.station_path {
+            info!("Config override: station_path = {}", path.display());
+            config.station_path = path;
+        }
+        if let Some(slugs) = file.station_slugs {
+            info!("Config override: station_slugs = {:?}", slugs);
+            config.station_slugs = slugs;
+        }
+        if let Some(path) = file.roster_path {
+            info!("Config override: roster_path = {}", path.display());
+            config.roster_path = Some(path);
+        }
+        if let Some(path) = file.ca_cert_path {
+            info!("Config override: ca_cert_path = {}", path.display());
+            config.ca_cert_path = Some(path);
+        }
+        if let Some(accept) = file.danger_accept_invalid_certs {
+            info!("Config override: danger_accept_invalid_certs = {}", accept);
+            config.danger_accept_invalid_certs = accept;
+        }
+
+        Ok(config)
+    }
+
+    // Reject values that parse but cannot work, naming every offending key at once so
+    // a field install is fixed in one pass. Called after command-line overrides.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut problems = Vec::new();
+
+        if self.scan_interval.is_zero() {
+            problems.push("scan_interval_ms must be greater than 0".to_string());
+        }
+        if self.stabilize_delay > self.stabilize_delay_max {
+            problems.push(format!(
+                "stabilize_delay_ms ({:?}) must not exceed stabilize_delay_max_ms ({:?})",
+                self.stabilize_delay, self.stabilize_delay_max
+            ));
+        }
+        if let Some(ms) = self.reader_poll_interval_ms.filter(|ms| *ms != 250 && *ms != 500) {
+            problems.push(format!("reader_poll_interval_ms must be 250 or 500, not {}", ms));
+        }
+        if self.reader_names.is_empty() {
+            problems.push("reader_names must list at least one reader".to_string());
+        } else if self.reader_names.iter().any(|name| name.trim().is_empty()) {
+            problems.push("reader_names must not contain empty names".to_string());
+        }
+        if !self.accept_any_uid_length {
+            if self.valid_uid_lengths.is_empty() {
+                problems.push("valid_uid_lengths is empty, so no card would be accepted".to_string());
+            }
+            if let Some(len) = self.valid_uid_lengths.iter().find(|len| !(1..=10).contains(*len)) {
+                problems.push(format!("valid_uid_lengths contains {}, UIDs are 1 to 10 bytes", len));
+            }
+        }
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            problems.push(format!("base_url {:?} must start with http:// or https://", self.base_url));
+        }
+        for (trivia, slug) in &self.checkpoint_slugs {
+            if trivia.trim().is_empty() {
+                problems.push(format!("checkpoint_slugs: slug {:?} has an empty trivia name", slug));
+            }
+            if slug.trim().is_empty() {
+                problems.push(format!("checkpoint_slugs: {:?} has an empty slug", trivia));
+            }
+        }
+        if self.request_timeout.is_zero() {
+            problems.push("request_timeout_ms must be greater than 0".to_string());
+        }
+        if self.max_response_bytes == 0 {
+            problems.push("max_response_kb must be greater than 0".to_string());
+        }
+        if self.retry.max_retries == 0 {
+            problems.push("max_retries must be at least 1 (it counts the first attempt)".to_string());
+        }
+        if self.retry.base_delay > self.retry.max_delay {
+            problems.push(format!(
+                "base_delay_ms ({:?}) must not exceed max_delay_ms ({:?})",
+                self.retry.base_delay, self.retry.max_delay
+            ));
+        }
+        // 401 must stay final: it pauses scanning until the token is replaced
+        if let Some(status) = self.retry.retry_statuses.iter().find(|status| !(402..=599).contains(*status)) {
+            problems.push(format!("retry_statuses contains {}, only 402-599 can be retried", status));
+        }
+        if self.queue_retry_interval.is_zero() {
+            problems.push("queue_retry_interval_secs must be greater than 0".to_string());
+        }
+        if self.queue_batch_size == 0 {
+            problems.push("queue_batch_size must be at least 1".to_string());
+        }
+        if self.lookup_workers == 0 {
+            problems.push("lookup_workers must be at least 1".to_string());
+        }
+        if self.max_batch_size == 0 {
+            problems.push("max_batch_size must be greater than 0".to_string());
+        }
+        if self.locale.trim().is_empty() {
+            problems.push("locale must not be empty".to_string());
+        }
+        if self.score_presets.iter().any(|preset| preset.label.trim().is_empty()) {
+            problems.push("score_presets entries need a label".to_string());
+        }
+        if self.write_stamp && self.stamp_block == 0 {
+            problems.push("stamp_block 0 holds the card UID and cannot be written".to_string());
+        }
+        // The settings keypad only has digits and takes at most 8 of them
+        if let Some(pin) = &self.settings_pin
+            && (pin.len() > 8 || !pin.chars().all(|c| c.is_ascii_digit()))
+        {
+            problems.push("settings_pin must be 1 to 8 digits".to_string());
+        }
+        if let Some(hash) = &self.lock_pin_sha256
+            && (hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            problems.push("lock_pin_sha256 must be a SHA-256 hex digest; generate it with `rpiui --hash-pin <pin>`".to_string());
+        }
+        if let Some(Err(e)) = self.open_hours.as_deref().map(crate::closed::parse_open_hours) {
+            problems.push(e);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Config(format!("Invalid config: {}", problems.join("; "))))
+        }
+    }
+
+    // Slug of one of this event's checkpoints, for lookups that only need the event
+    // (theme, selfcheck). Picks the first trivia name so the choice is stable.
+    pub fn event_slug(&self) -> Option<&str> {
+        self.first_trivia().and_then(|trivia| self.checkpoint_slugs.get(trivia)).map(String::as_str)
+    }
+
+    // First trivia name in checkpoint_slugs, which one-tap stations (use_checkpoint_score) score for
+    pub fn first_trivia(&self) -> Option<&str> {
+        self.trivia_names().first().copied()
+    }
+
+    // The trivias the welcome screen and the keypad offer, one per checkpoint_slugs key,
+    // in name order
+    pub fn trivia_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.checkpoint_slugs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // Whether a UID of `len` bytes is accepted: any non-empty UID with
+    // accept_any_uid_length, otherwise only the lengths in valid_uid_lengths
+    pub fn uid_length_ok(&self, len: usize) -> bool {
+        if self.accept_any_uid_length {
+            len > 0
+        } else {
+            self.valid_uid_lengths.contains(&len)
+        }
+    }
+
+    // UID lengths to pass to nfc::parse_uid_response; empty (any length) with accept_any_uid_length
+    pub fn accepted_uid_lengths(&self) -> &[usize] {
+        if self.accept_any_uid_length {
+            &[]
+        } else {
+            &self.valid_uid_lengths
+        }
+    }
+
+    // Join an endpoint path such as "control/guests" onto the API base URL
+    pub fn endpoint_url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+}
+
+// Install the loaded config; must be called once at startup before `config()` is used
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        warn!("Config already initialized, ignoring reload");
+    }
+}
+
+// Access the global config, falling back to defaults if `init` was never called
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+// Slug currently used for `trivia`, including runtime overrides
+pub fn checkpoint_slug(trivia: &str) -> Option<String> {
+    let overrides = SLUG_OVERRIDES.read().unwrap();
+    overrides
+        .as_ref()
+        .and_then(|o| o.get(trivia))
+        .or_else(|| config().checkpoint_slugs.get(trivia))
+        .cloned()
+}
+
+// Every trivia's current slug, including runtime overrides
+pub fn checkpoint_slugs() -> HashMap<String, String> {
+    let mut slugs = config().checkpoint_slugs.clone();
+    if let Some(overrides) = SLUG_OVERRIDES.read().unwrap().as_ref() {
+        slugs.extend(overrides.clone());
+    }
+    slugs
+}
+
+// Point `trivia` at another checkpoint until the next restart
+pub fn set_checkpoint_slug(trivia: &str, slug: &str) {
+    SLUG_OVERRIDES
+        .write()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(trivia.to_string(), slug.to_string());
+}
+
+// Token file read from the working directory when RPIUI_ACCESS_TOKEN is not set
+pub const TOKEN_FILE: &str = "token.txt";
+
+// Modification time of the token file, if it exists
+pub fn token_file_modified() -> Option<SystemTime> {
+    std::fs::metadata(TOKEN_FILE).and_then(|m| m.modified()).ok()
+}
+
+// Read the API access token from --token, then RPIUI_ACCESS_TOKEN, falling back to
+// token.txt in the working directory
+pub fn load_access_token() -> Result<String, AppError> {
+    if let Some(token) = config().access_token.clone().filter(|token| !token.is_empty()) {
+        return Ok(token);
+    }
+    if let Ok(token) = std::env::var("RPIUI_ACCESS_TOKEN") {
+        let token = token.trim().to_string();
+        if !token.is_empty() {
+            info!("Access token {} loaded from RPIUI_ACCESS_TOKEN", crate::api::redact(&token));
+            return Ok(token);
+        }
+    }
+
+    match std::fs::read_to_string(TOKEN_FILE) {
+        Ok(contents) if !contents.trim().is_empty() => {
+            let token = contents.trim().to_string();
+            info!("Access token {} loaded from token.txt", crate::api::redact(&token));
+            Ok(token)
+        }
+        _ => Err(AppError::InvalidInput(
+            "Access token not found: set RPIUI_ACCESS_TOKEN or create token.txt".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Load `contents` as a config file through the normal path
+    fn load(name: &str, contents: &str) -> Result<Config, AppError> {
+        let path = std::env::temp_dir().join(format!("rpiui-config-{}-{}.toml", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        let result = Config::load_from_path(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn backoff_settings_come_from_the_file() {
+        let config = load(
+            "backoff",
+            "max_retries = 5\nbase_delay_ms = 250\nmax_delay_ms = 2000\nretry_jitter = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.retry.max_retries, 5);
+        assert_eq!(config.retry.base_delay, Duration::from_millis(250));
+        assert_eq!(config.retry.max_delay, Duration::from_secs(2));
+        assert!(!config.retry.jitter);
+        // Keys left out keep their defaults
+        assert_eq!(config.retry.retry_statuses, RetryConfig::default().retry_statuses);
+    }
+
+    #[test]
+    fn retry_delays_stay_within_the_configured_cap() {
+        for jitter in [false, true] {
+            let config = load(
+                &format!("cap-{}", jitter),
+                &format!("base_delay_ms = 300\nmax_delay_ms = 1000\nretry_jitter = {}\n", jitter),
+            )
+            .unwrap();
+            for attempt in 1..=40 {
+                let delay = crate::api::retry_delay(attempt, &config.retry);
+                assert!(delay <= config.retry.max_delay, "attempt {}: {:?}", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_file_gives_a_valid_config_without_trivias() {
+        let path = std::env::temp_dir().join(format!("rpiui-config-{}-missing.toml", std::process::id()));
+        let config = Config::load_from_path(&path).unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config.trivia_names().is_empty());
+    }
+
+    #[test]
+    fn trivias_are_the_checkpoint_slugs_keys() {
+        let config = load(
+            "trivias",
+            "[checkpoint_slugs]\n\"TU SALA FAVORITA\" = \"trivia-dos\"\n\"TU CALLROOM FAVORITO\" = \"trivia-uno\"\n\"QUIZ 3\" = \"trivia-tres\"\n",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.trivia_names(), ["QUIZ 3", "TU CALLROOM FAVORITO", "TU SALA FAVORITA"]);
+        assert_eq!(config.first_trivia(), Some("QUIZ 3"));
+    }
+
+    #[test]
+    fn validate_reports_every_problem_at_once() {
+        let config = Config {
+            scan_interval: Duration::ZERO,
+            stabilize_delay: Duration::from_secs(2),
+            stabilize_delay_max: Duration::from_secs(1),
+            reader_poll_interval_ms: Some(300),
+            reader_names: Vec::new(),
+            valid_uid_lengths: vec![4, 12],
+            base_url: "wonderlab.events/api".to_string(),
+            checkpoint_slugs: HashMap::from([
+                ("TRIVIA 1".to_string(), " ".to_string()),
+                ("TRIVIA 2".to_string(), "trivia-dos".to_string()),
+            ]),
+            request_timeout: Duration::ZERO,
+            max_response_bytes: 0,
+            retry: RetryConfig {
+                max_retries: 0,
+                base_delay: Duration::from_secs(60),
+                max_delay: Duration::from_secs(30),
+                retry_statuses: vec![401],
+                ..RetryConfig::default()
+            },
+            queue_retry_interval: Duration::ZERO,
+            queue_batch_size: 0,
+            lookup_workers: 0,
+            max_batch_size: 0,
+            locale: " ".to_string(),
+            score_presets: vec![ScorePreset { label: String::new(), value: 5 }],
+            write_stamp: true,
+            stamp_block: 0,
+            settings_pin: Some("12ab".to_string()),
+            lock_pin_sha256: Some("abc".to_string()),
+            open_hours: Some("9am-5pm".to_string()),
+            ..Config::default()
+        };
+        let message = config.validate().unwrap_err().to_string();
+        for key in [
+            "scan_interval_ms",
+            "stabilize_delay_ms",
+            "reader_poll_interval_ms",
+            "reader_names",
+            "valid_uid_lengths contains 12",
+            "base_url",
+            r#"checkpoint_slugs: "TRIVIA 1" has an empty slug"#,
+            "request_timeout_ms",
+            "max_response_kb",
+            "max_retries",
+            "base_delay_ms",
+            "retry_statuses contains 401",
+            "queue_retry_interval_secs",
+            "queue_batch_size",
+            "lookup_workers",
+            "max_batch_size",
+            "locale",
+            "score_presets",
+            "stamp_block",
+            "settings_pin",
+            "lock_pin_sha256",
+            "open_hours",
+        ] {
+            assert!(message.contains(key), "{:?} missing from {}", key, message);
+        }
+    }
+}
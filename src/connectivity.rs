@@ -0,0 +1,115 @@
+use log::{error, info, warn};
+use slint::Weak;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::api::ApiClient;
+use crate::config::config;
+use crate::token;
+use crate::{AppError, AppWindow};
+
+// Consecutive network failures before the kiosk is shown as offline
+const OFFLINE_AFTER_FAILURES: u32 = 2;
+
+// Online/offline state derived from the outcome of recent API calls
+pub struct Connectivity {
+    online: AtomicBool,
+    failures: AtomicU32,
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Connectivity {
+            online: AtomicBool::new(true),
+            failures: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Connectivity {
+    // Any answer from the server means we are online; only transport errors
+    // (timeouts, refused connections, DNS) count towards going offline
+    pub fn record<T>(&self, result: &Result<T, AppError>) {
+        match result {
+            Ok(_) => {
+                self.failures.store(0, Ordering::SeqCst);
+                if !self.online.swap(true, Ordering::SeqCst) {
+                    info!("API reachable again");
+                }
+            }
+            Err(AppError::Http(_) | AppError::DeadlineExceeded(_)) => {
+                let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+                if failures >= OFFLINE_AFTER_FAILURES && self.online.swap(false, Ordering::SeqCst) {
+                    warn!("API unreachable after {} failed requests", failures);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+}
+
+// Background thread that mirrors the connectivity state into the UI's `online` property
+pub fn spawn_indicator(connectivity: Arc<Connectivity>, ui_handle: Weak<AppWindow>) {
+    thread::spawn(move || {
+        let mut shown = true;
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let online = connectivity.is_online();
+            if online == shown {
+                continue;
+            }
+            shown = online;
+
+            let weak = ui_handle.clone();
+            slint::invoke_from_event_loop(move || {
+                if let Some(ui) = weak.upgrade() {
+                    ui.set_online(online);
+                }
+            }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+        }
+    });
+}
+
+// Background thread that pings the API every heartbeat_interval, keeping the online
+// indicator current between guests. An expired token raises the usual banner and pauses
+// scans once; a ping the API accepts again resumes them, e.g. after the token was
+// renewed on the server side.
+pub fn spawn_heartbeat(api: Arc<dyn ApiClient>, ui_handle: Weak<AppWindow>) {
+    let interval = config().heartbeat_interval;
+    if interval.is_zero() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut token_expired = false;
+        loop {
+            thread::sleep(interval);
+            match api.ping() {
+                Err(AppError::TokenExpired) => {
+                    if !token_expired {
+                        warn!("Heartbeat: access token expired");
+                        token::expired(&ui_handle);
+                    }
+                    token_expired = true;
+                }
+                Err(e) => {
+                    warn!("Heartbeat failed: {}", e);
+                    token_expired = false;
+                }
+                Ok(()) => {
+                    token_expired = false;
+                    if token::scans_paused() {
+                        info!("Heartbeat: access token accepted again");
+                        token::resume(&ui_handle);
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,176 @@
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use slint::Weak;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::api::ApiClient;
+use crate::closed;
+use crate::config::{self, config};
+use crate::connectivity::Connectivity;
+use crate::metrics::Metrics;
+use crate::nfc;
+use crate::queue::ScoreQueue;
+use crate::token;
+use crate::{AppError, AppWindow};
+
+// Largest request body accepted; the endpoints only take small JSON objects
+const MAX_BODY: usize = 4096;
+
+// Shared state the control endpoints act on
+pub struct ControlState {
+    pub api: Arc<dyn ApiClient>,
+    pub connectivity: Arc<Connectivity>,
+    pub score_queue: Arc<ScoreQueue>,
+    pub metrics: Arc<Metrics>,
+    pub ui_handle: Weak<AppWindow>,
+}
+
+#[derive(Deserialize)]
+struct CheckpointSwap {
+    trivia: String,
+    slug: String,
+}
+
+#[derive(Deserialize)]
+struct ClosedSwitch {
+    closed: bool,
+}
+
+// --control-port: a small HTTP server for fleet management, bound to control_bind
+// (localhost by default). Endpoints:
+//   GET  /status        reader present, online, queue depth, active slugs and metrics
+//   POST /reload-token  re-read RPIUI_ACCESS_TOKEN / token.txt and resume paused scans
+//   POST /checkpoint    {"trivia": "TU SALA FAVORITA", "slug": "..."} swaps a trivia's slug
+//   POST /closed        {"closed": true} closes the station, false opens it again
+// Requests are handled one at a time on a single background thread.
+pub fn spawn(port: u16, state: ControlState) -> std::io::Result<()> {
+    let listener = TcpListener::bind((config().control_bind.as_str(), port))?;
+    info!("Control endpoint listening on {}:{}", config().control_bind, port);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle(stream, &state) {
+                        warn!("Control request failed: {}", e);
+                    }
+                }
+                Err(e) => error!("Control connection error: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(stream: TcpStream, state: &ControlState) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    let (status, body) = if content_length > MAX_BODY {
+        (413, json!({ "error": "request body too large" }))
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        info!("Control request: {} {}", method, path);
+        route(&method, &path, &body, state)
+    };
+    respond(stream, status, &body)
+}
+
+fn route(method: &str, path: &str, body: &[u8], state: &ControlState) -> (u16, Value) {
+    match (method, path) {
+        ("GET", "/status") => (200, status(state)),
+        ("POST", "/reload-token") => match token::reload(state.api.as_ref(), &state.ui_handle) {
+            Ok(()) => {
+                info!("Access token reloaded through the control endpoint");
+                (200, json!({ "ok": true }))
+            }
+            Err(e) => (500, json!({ "error": e.to_string() })),
+        },
+        ("POST", "/checkpoint") => swap_checkpoint(body, state),
+        ("POST", "/closed") => match serde_json::from_slice::<ClosedSwitch>(body) {
+            Ok(switch) => {
+                closed::set_closed(&state.ui_handle, switch.closed, "the control endpoint");
+                (200, json!({ "closed": switch.closed }))
+            }
+            Err(e) => (400, json!({ "error": format!("invalid body: {}", e) })),
+        },
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+fn status(state: &ControlState) -> Value {
+    let reader_present = nfc::list_matching_readers().map(|r| !r.is_empty()).unwrap_or(false);
+    json!({
+        "reader_present": reader_present,
+        "online": state.connectivity.is_online(),
+        "closed": closed::is_closed(),
+        "queue_depth": state.score_queue.pending(),
+        "checkpoint_slugs": config::checkpoint_slugs(),
+        "metrics": state.metrics.snapshot(),
+    })
+}
+
+// The new slug must resolve before it replaces the old one
+fn swap_checkpoint(body: &[u8], state: &ControlState) -> (u16, Value) {
+    let swap: CheckpointSwap = match serde_json::from_slice(body) {
+        Ok(swap) => swap,
+        Err(e) => return (400, json!({ "error": format!("invalid body: {}", e) })),
+    };
+    if config::checkpoint_slug(&swap.trivia).is_none() {
+        return (404, json!({ "error": format!("unknown trivia {:?}", swap.trivia) }));
+    }
+    match state.api.get_checkpoint(&swap.slug) {
+        Ok(resp) => {
+            config::set_checkpoint_slug(&swap.trivia, &swap.slug);
+            info!("{} now uses checkpoint {} ({})", swap.trivia, resp.checkpoint.id, swap.slug);
+            (200, json!({ "trivia": swap.trivia, "checkpoint": resp.checkpoint }))
+        }
+        Err(e @ AppError::CheckpointNotFound { .. }) => (404, json!({ "error": e.to_string() })),
+        Err(e) => (502, json!({ "error": e.to_string() })),
+    }
+}
+
+fn respond(mut stream: TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        502 => "Bad Gateway",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,86 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::config::config;
+use crate::queue::ScoreQueue;
+use crate::AppError;
+
+// --export <path>: everything recorded on this kiosk as JSON lines, for bulk import
+// after an event. One object per line, told apart by "record":
+//
+//   {"record":"audit","timestamp":"2025-03-01T18:04:11-03:00","kind":"scan"|"score",
+//    "reader":"ACS ACR122U..."|null,"uid":"04A1B2C3","guest_name":"Ana"|null,
+//    "checkpoint_id":546|null,"score":"120"|null,"outcome":"ok"}
+//   {"record":"pending_score","checkpoint_id":546,"guest_tag":"04A1B2C3","score":"120"}
+//
+// Audit records come first, oldest first, and cover every scan and submission, whatever
+// the outcome. pending_score records are the queued submissions the server has not
+// accepted yet; importing those is what recovers scores sync never delivered.
+// Fields are only ever added to this format, never renamed or removed.
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum ExportRecord {
+    Audit(AuditRecord),
+    PendingScore {
+        checkpoint_id: i32,
+        guest_tag: String,
+        score: String,
+    },
+}
+
+// One audit log line, as written by audit::write
+#[derive(Serialize, Deserialize)]
+struct AuditRecord {
+    timestamp: String,
+    kind: String,
+    reader: Option<String>,
+    uid: String,
+    guest_name: Option<String>,
+    checkpoint_id: Option<i32>,
+    score: Option<String>,
+    outcome: String,
+}
+
+pub fn run(path: &Path) -> Result<(), AppError> {
+    let write_err = |e: std::io::Error| AppError::Config(format!("Failed to write {}: {}", path.display(), e));
+    let mut out = BufWriter::new(File::create(path).map_err(write_err)?);
+
+    let mut audit_count = 0;
+    match std::fs::read_to_string(&config().audit_log_path) {
+        Ok(contents) => {
+            for (n, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match serde_json::from_str::<AuditRecord>(line) {
+                    Ok(record) => {
+                        write_record(&mut out, &ExportRecord::Audit(record)).map_err(write_err)?;
+                        audit_count += 1;
+                    }
+                    Err(e) => warn!("Skipping unreadable audit line {}: {}", n + 1, e),
+                }
+            }
+        }
+        Err(e) => warn!("No audit log exported, cannot read {}: {}", config().audit_log_path.display(), e),
+    }
+
+    let pending = ScoreQueue::open(&config().queue_path)?.entries();
+    for entry in &pending {
+        let record = ExportRecord::PendingScore {
+            checkpoint_id: entry.checkpoint_id,
+            guest_tag: entry.guest_tag.clone(),
+            score: entry.score.clone(),
+        };
+        write_record(&mut out, &record).map_err(write_err)?;
+    }
+    out.flush().map_err(write_err)?;
+
+    info!("Exported {} audit records and {} pending scores to {}", audit_count, pending.len(), path.display());
+    println!("Exported {} audit records and {} pending scores to {}", audit_count, pending.len(), path.display());
+    Ok(())
+}
+
+fn write_record(out: &mut impl Write, record: &ExportRecord) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    writeln!(out)
+}
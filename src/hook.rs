@@ -0,0 +1,151 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::process::Command;
+use std::thread;
+
+use crate::api::LoadScorePostPayload;
+use crate::config::config;
+
+// What a successful score is reported as, to the webhook as JSON and to the command
+// as {name}, {tag}, {checkpoint}, {checkpoint_id} and {score}
+#[derive(Serialize)]
+pub struct ScoreEvent {
+    pub name: String,
+    pub tag: String,
+    pub checkpoint: String,
+    pub checkpoint_id: i32,
+    pub score: String,
+}
+
+// A queued or batched score the drainer got accepted
+impl From<&LoadScorePostPayload> for ScoreEvent {
+    fn from(payload: &LoadScorePostPayload) -> Self {
+        ScoreEvent {
+            name: payload.guest_name.clone(),
+            tag: payload.guest_tag.clone(),
+            checkpoint: payload.checkpoint_name.clone(),
+            checkpoint_id: payload.checkpoint_id,
+            score: payload.score.clone(),
+        }
+    }
+}
+
+// Fire on_success_hook for a newly loaded score, if one is configured. Runs on its own
+// thread so a slow printer or light never holds up the kiosk; failures are only logged.
+pub fn on_success(event: ScoreEvent) {
+    let Some(hook) = config().on_success_hook.clone() else {
+        return;
+    };
+    thread::spawn(move || {
+        let result = if hook.starts_with("http://") || hook.starts_with("https://") {
+            post_webhook(&hook, &event)
+        } else {
+            run_command(&hook, &event)
+        };
+        match result {
+            Ok(()) => info!("Success hook ran for {}", event.tag),
+            Err(e) => warn!("Success hook failed for {}: {}", event.tag, e),
+        }
+    });
+}
+
+fn post_webhook(url: &str, event: &ScoreEvent) -> Result<(), String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(config().request_timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.post(url).json(event).send().map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("{} answered {}", url, response.status()))
+    }
+}
+
+// The template runs through `sh -c`; substituted values are single-quoted so a guest
+// name can never inject shell syntax
+fn run_command(template: &str, event: &ScoreEvent) -> Result<(), String> {
+    let command = expand(template, event);
+    let status = Command::new("sh").arg("-c").arg(&command).status().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("command exited with {}", status))
+    }
+}
+
+// Replace the placeholders in one pass over the template, so a value that itself
+// contains "{tag}" or the like is inserted as is. Unknown placeholders are left alone.
+fn expand(template: &str, event: &ScoreEvent) -> String {
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        command.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let value = rest.find('}').and_then(|close| Some((placeholder(&rest[1..close], event)?, close)));
+        match value {
+            Some((value, close)) => {
+                command.push_str(&value);
+                rest = &rest[close + 1..];
+            }
+            None => {
+                command.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    command.push_str(rest);
+    command
+}
+
+fn placeholder(name: &str, event: &ScoreEvent) -> Option<String> {
+    Some(match name {
+        "name" => shell_quote(&event.name),
+        "tag" => shell_quote(&event.tag),
+        "checkpoint" => shell_quote(&event.checkpoint),
+        "checkpoint_id" => event.checkpoint_id.to_string(),
+        "score" => shell_quote(&event.score),
+        _ => return None,
+    })
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> ScoreEvent {
+        ScoreEvent {
+            name: name.to_string(),
+            tag: "04A1B2C3".to_string(),
+            checkpoint: "Trivia 1".to_string(),
+            checkpoint_id: 62,
+            score: "10".to_string(),
+        }
+    }
+
+    #[test]
+    fn placeholders_are_replaced_and_quoted() {
+        assert_eq!(
+            expand("print {name} {tag} {checkpoint} {checkpoint_id} {score}", &event("Ana")),
+            "print 'Ana' '04A1B2C3' 'Trivia 1' 62 '10'"
+        );
+        assert_eq!(expand("say {name}", &event("O'Brien")), r"say 'O'\''Brien'");
+    }
+
+    #[test]
+    fn placeholders_inside_values_are_not_expanded() {
+        assert_eq!(expand("say {name} {tag}", &event("{tag} $(reboot)")), "say '{tag} $(reboot)' '04A1B2C3'");
+        assert_eq!(expand("say {name}", &event("{score}")), "say '{score}'");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_braces_are_kept() {
+        assert_eq!(expand("awk '{print}' {score}", &event("Ana")), "awk '{print}' '10'");
+        assert_eq!(expand("echo {score", &event("Ana")), "echo {score");
+        assert_eq!(expand("{{score}}", &event("Ana")), "{'10'}");
+    }
+}
@@ -0,0 +1,64 @@
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// Operator-facing messages for the configured locale, loaded from lang/<locale>.toml.
+// Keys missing from the catalog (or no catalog at all, as for "en") use the English default.
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// Load the catalog for `locale` from `lang_dir`; must be called once at startup
+pub fn init(lang_dir: &Path, locale: &str) {
+    let path = lang_dir.join(format!("{}.toml", locale));
+    let catalog = match std::fs::read_to_string(&path) {
+        Ok(contents) => match toml::from_str::<HashMap<String, String>>(&contents) {
+            Ok(catalog) => {
+                info!("Loaded {} messages for locale {:?}", catalog.len(), locale);
+                catalog
+            }
+            Err(e) => {
+                warn!("Failed to parse {}: {}, using English", path.display(), e);
+                HashMap::new()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if locale != "en" {
+                warn!("No message catalog at {}, using English", path.display());
+            }
+            HashMap::new()
+        }
+        Err(e) => {
+            warn!("Failed to read {}: {}, using English", path.display(), e);
+            HashMap::new()
+        }
+    };
+    let _ = CATALOG.set(catalog);
+}
+
+// Look up `key`, falling back to the built-in English text
+pub fn tr(key: &str, default: &str) -> String {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(key))
+        .cloned()
+        .unwrap_or_else(|| default.to_string())
+}
+
+// Like `tr`, filling each "{}" in the message with `args` in order
+pub fn trf(key: &str, default: &str, args: &[&dyn Display]) -> String {
+    let template = tr(key, default);
+    let mut message = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template.as_str();
+    while let Some(pos) = rest.find("{}") {
+        message.push_str(&rest[..pos]);
+        match args.next() {
+            Some(arg) => message.push_str(&arg.to_string()),
+            None => message.push_str("{}"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    message.push_str(rest);
+    message
+}
@@ -0,0 +1,66 @@
+use log::{error, info};
+use slint::{SharedString, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::config;
+use crate::team;
+use crate::AppWindow;
+
+// Tracks the last card/score activity so the UI can fall back to the idle screen
+pub struct IdleTracker {
+    last_activity: Mutex<Instant>,
+    idle: AtomicBool,
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        IdleTracker {
+            last_activity: Mutex::new(Instant::now()),
+            idle: AtomicBool::new(false),
+        }
+    }
+}
+
+impl IdleTracker {
+    // Record activity, cancelling any pending return to idle
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+        self.idle.store(false, Ordering::SeqCst);
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+}
+
+// Background thread that resets the UI once idle_timeout passes without activity
+pub fn spawn_idle_watcher(tracker: Arc<IdleTracker>, ui_handle: Weak<AppWindow>) {
+    let timeout = config().idle_timeout;
+    if timeout.is_zero() {
+        info!("Idle timeout disabled");
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if tracker.idle.load(Ordering::SeqCst) || tracker.idle_for() < timeout {
+            continue;
+        }
+        tracker.idle.store(true, Ordering::SeqCst);
+        info!("No activity for {:?}, returning to idle", timeout);
+
+        let weak = ui_handle.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(ui) = weak.upgrade() {
+                ui.set_current_screen(SharedString::from("idle"));
+                ui.set_user_name(SharedString::new());
+                ui.set_card_uid(SharedString::new());
+                ui.set_error_message(SharedString::new());
+                team::reset(&ui);
+            }
+        }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+    });
+}
@@ -0,0 +1,75 @@
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use slint::Weak;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::config;
+use crate::idle::IdleTracker;
+use crate::AppWindow;
+
+// Operator lock. With lock_pin_sha256 set the kiosk starts locked and ignores cards
+// until the PIN is entered, and locks itself again after lock_timeout without activity.
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_locked() -> bool {
+    LOCKED.load(Ordering::SeqCst)
+}
+
+// Lowercase hex SHA-256 of a PIN, as lock_pin_sha256 expects; see --hash-pin
+pub fn hash_pin(pin: &str) -> String {
+    Sha256::digest(pin.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Lock at startup when a PIN is configured. Must be called on the UI thread.
+pub fn init(ui: &AppWindow) {
+    if config().lock_pin_sha256.is_some() {
+        info!("Operator lock enabled, starting locked");
+        set_locked(ui, true);
+    }
+}
+
+// Unlock if `pin` matches lock_pin_sha256; called from the lock screen keypad
+pub fn try_unlock(ui: &AppWindow, idle: &IdleTracker, pin: &str) -> bool {
+    let matches = config().lock_pin_sha256.as_deref().is_some_and(|expected| hash_pin(pin) == expected);
+    if matches {
+        info!("Kiosk unlocked");
+        idle.touch();
+        set_locked(ui, false);
+    } else {
+        warn!("Wrong operator PIN entered");
+    }
+    matches
+}
+
+fn set_locked(ui: &AppWindow, locked: bool) {
+    LOCKED.store(locked, Ordering::SeqCst);
+    ui.set_locked(locked);
+}
+
+// Background thread that locks the kiosk again once lock_timeout passes without a scan
+// or score. Does nothing without a lock PIN or with lock_timeout_secs = 0.
+pub fn spawn_auto_lock(tracker: Arc<IdleTracker>, ui_handle: Weak<AppWindow>) {
+    let timeout = config().lock_timeout;
+    if config().lock_pin_sha256.is_none() || timeout.is_zero() {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if is_locked() || tracker.idle_for() < timeout {
+            continue;
+        }
+        info!("No activity for {:?}, locking the kiosk", timeout);
+        LOCKED.store(true, Ordering::SeqCst);
+
+        let weak = ui_handle.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(ui) = weak.upgrade() {
+                ui.set_locked(true);
+            }
+        }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+    });
+}
@@ -0,0 +1,85 @@
+use log::info;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::config;
+
+// Session counters for monitoring and post-event analysis, shared by the
+// scanner, the API client and the control endpoint. Lock-free: every field
+// is an independent atomic, so a snapshot may be a few events out of step.
+#[derive(Default)]
+pub struct Metrics {
+    scans: AtomicU64,
+    lookups_ok: AtomicU64,
+    lookups_failed: AtomicU64,
+    retries: AtomicU64,
+    requests: AtomicU64,
+    latency_ms_total: AtomicU64,
+}
+
+// Point-in-time copy of the counters, as reported by /status and the log line
+#[derive(Serialize, Debug)]
+pub struct MetricsSnapshot {
+    pub scans: u64,
+    pub lookups_ok: u64,
+    pub lookups_failed: u64,
+    pub retries: u64,
+    pub requests: u64,
+    pub avg_latency_ms: u64,
+}
+
+impl Metrics {
+    // A card was read (or simulated) and is about to be looked up
+    pub fn record_scan(&self) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_lookup(&self, ok: bool) {
+        let counter = if ok { &self.lookups_ok } else { &self.lookups_failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // A request attempt failed and is about to be sent again
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // One HTTP attempt that got an answer or a transport error
+    pub fn record_request(&self, elapsed: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency_ms_total.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let latency_ms_total = self.latency_ms_total.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            scans: self.scans.load(Ordering::Relaxed),
+            lookups_ok: self.lookups_ok.load(Ordering::Relaxed),
+            lookups_failed: self.lookups_failed.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            requests,
+            avg_latency_ms: latency_ms_total.checked_div(requests).unwrap_or(0),
+        }
+    }
+}
+
+// Background thread that logs the counters every metrics_log_interval
+pub fn spawn_logger(metrics: Arc<Metrics>) {
+    let interval = config().metrics_log_interval;
+    if interval.is_zero() {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let m = metrics.snapshot();
+        info!(
+            "Metrics: {} scans, {} lookups ok, {} failed, {} retries, {} requests, avg latency {} ms",
+            m.scans, m.lookups_ok, m.lookups_failed, m.retries, m.requests, m.avg_latency_ms
+        );
+    });
+}
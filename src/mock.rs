@@ -0,0 +1,126 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::api::{self, ApiClient, Checkpoint, Guest, GuestsPostResponse, LoadScorePostResponse, PostResponse, VisualConfig};
+use crate::AppError;
+
+// What the fake server answers to one request
+#[derive(Debug, Clone)]
+pub enum Reply<T> {
+    Body(T),
+    Status(u16),
+    // No answer before the deadline
+    Offline,
+}
+
+impl<T> Reply<T> {
+    fn into_result(self) -> Result<T, AppError> {
+        match self {
+            Reply::Body(body) => Ok(body),
+            Reply::Status(status) => Err(AppError::ApiError {
+                status,
+                message: format!("mock status {}", status),
+            }),
+            Reply::Offline => Err(AppError::DeadlineExceeded(Duration::from_secs(8))),
+        }
+    }
+}
+
+// In-memory ApiClient for tests. Replies are what the server would send and go through
+// the same mapping as HttpApi, so a 404 for a guest comes back as GuestNotFound.
+// Unknown slugs and tags answer 404; load_score accepts once a tag's replies run out.
+#[derive(Default)]
+pub struct MockApi {
+    checkpoints: HashMap<String, Reply<PostResponse>>,
+    guests: HashMap<String, Reply<Vec<Guest>>>,
+    scores: Mutex<HashMap<String, VecDeque<Reply<LoadScorePostResponse>>>>,
+    // Every load_score call as (guest_tag, score), in order
+    pub sent: Mutex<Vec<(String, String)>>,
+}
+
+impl MockApi {
+    pub fn new() -> MockApi {
+        MockApi::default()
+    }
+
+    pub fn with_checkpoint(mut self, slug: &str, reply: Reply<PostResponse>) -> MockApi {
+        self.checkpoints.insert(slug.to_string(), reply);
+        self
+    }
+
+    pub fn with_guests(mut self, tag: &str, reply: Reply<Vec<Guest>>) -> MockApi {
+        self.guests.insert(tag.to_string(), reply);
+        self
+    }
+
+    // Replies to the next load_score calls for `tag`, in order
+    pub fn with_scores(self, tag: &str, replies: Vec<Reply<LoadScorePostResponse>>) -> MockApi {
+        self.scores.lock().unwrap().insert(tag.to_string(), replies.into());
+        self
+    }
+
+    pub fn sent_tags(&self) -> Vec<String> {
+        self.sent.lock().unwrap().iter().map(|(tag, _)| tag.clone()).collect()
+    }
+}
+
+pub fn checkpoint(id: i32, slug: &str, repetible: i32) -> Checkpoint {
+    Checkpoint {
+        event_id: 1,
+        id,
+        name: format!("Checkpoint {}", id),
+        repetible,
+        score: 10,
+        slug: slug.to_string(),
+        extra: serde_json::Value::Null,
+    }
+}
+
+pub fn guest(name: &str, tag: Option<&str>) -> Guest {
+    Guest {
+        name: name.to_string(),
+        tag: tag.map(str::to_string),
+        other: serde_json::Value::Null,
+    }
+}
+
+impl ApiClient for MockApi {
+    fn get_checkpoint(&self, slug: &str) -> Result<PostResponse, AppError> {
+        let reply = self.checkpoints.get(slug).cloned().unwrap_or(Reply::Status(404));
+        api::checkpoint_result(reply.into_result(), slug)
+    }
+
+    fn get_guests(&self, guest_tag: &str) -> Result<GuestsPostResponse, AppError> {
+        let reply = self.guests.get(guest_tag).cloned().unwrap_or(Reply::Status(404));
+        api::guests_result(reply.into_result().map(|guests| GuestsPostResponse { guests }))
+    }
+
+    fn load_score(
+        &self,
+        _checkpoint_id: i32,
+        guest_tag: &str,
+        score: &str,
+        _idempotency_key: &str,
+    ) -> Result<LoadScorePostResponse, AppError> {
+        self.sent.lock().unwrap().push((guest_tag.to_string(), score.to_string()));
+        let reply = self
+            .scores
+            .lock()
+            .unwrap()
+            .get_mut(guest_tag)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or(Reply::Body(LoadScorePostResponse::default()));
+        api::load_score_result(reply.into_result())
+    }
+
+    fn get_visual(&self, _event_id: i32) -> Result<VisualConfig, AppError> {
+        Ok(VisualConfig::default())
+    }
+
+    fn ping(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn set_access_token(&self, _access_token: String) {}
+}
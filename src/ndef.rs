@@ -0,0 +1,184 @@
+use log::debug;
+use pcsc::Card;
+
+use crate::AppError;
+
+// NFC Forum Type 2 tags (NTAG, MIFARE Ultralight): 4-byte pages, the capability
+// container in page 3 and the NDEF TLVs from page 4 on
+const CC_PAGE: u8 = 3;
+const DATA_PAGE: u8 = 4;
+const CC_MAGIC: u8 = 0xE1;
+
+const TLV_NULL: u8 = 0x00;
+const TLV_NDEF: u8 = 0x03;
+const TLV_TERMINATOR: u8 = 0xFE;
+
+// Well-known record type (TNF 1)
+const TNF_WELL_KNOWN: u8 = 0x01;
+
+// URI record identifier codes, NFC Forum URI RTD table 3
+const URI_PREFIXES: [&str; 36] = [
+    "", "http://www.", "https://www.", "http://", "https://", "tel:", "mailto:",
+    "ftp://anonymous:anonymous@", "ftp://ftp.", "ftps://", "sftp://", "smb://", "nfs://",
+    "ftp://", "dav://", "news:", "telnet://", "imap:", "rtsp://", "urn:", "pop:", "sip:",
+    "sips:", "tftp:", "btspp://", "btl2cap://", "btgoep://", "tcpobex://", "irdaobex://",
+    "file://", "urn:epc:id:", "urn:epc:tag:", "urn:epc:pat:", "urn:epc:raw:", "urn:epc:",
+    "urn:nfc:",
+];
+
+// Read the NDEF message from a Type 2 tag and return the first text or URI record.
+// Ok(None) when the card is not a formatted Type 2 tag or holds no such record;
+// a malformed message is an AppError::Ndef.
+pub fn read_ndef(card: &Card) -> Result<Option<String>, AppError> {
+    let Some(cc) = read_pages(card, CC_PAGE)? else {
+        return Ok(None);
+    };
+    if cc[0] != CC_MAGIC {
+        debug!("No NDEF capability container (CC {:02X?})", &cc[..4]);
+        return Ok(None);
+    }
+    // Data area size in bytes is CC byte 2 times 8
+    let data_size = cc[2] as usize * 8;
+
+    let mut data = Vec::with_capacity(data_size);
+    let mut page = DATA_PAGE;
+    while data.len() < data_size {
+        match read_pages(card, page)? {
+            Some(block) => data.extend_from_slice(&block),
+            None => break,
+        }
+        // Stop as soon as the whole message is in
+        if find_ndef_tlv(&data).is_some() {
+            break;
+        }
+        page = page.saturating_add(4);
+    }
+    data.truncate(data_size);
+
+    match find_ndef_tlv(&data) {
+        Some(message) => parse_message(message),
+        None => Ok(None),
+    }
+}
+
+// READ BINARY of 16 bytes (four pages) from `page`; None when the card refuses it,
+// as non-Type 2 cards do
+fn read_pages(card: &Card, page: u8) -> Result<Option<[u8; 16]>, AppError> {
+    let apdu = [0xFF, 0xB0, 0x00, page, 0x10];
+    let mut recv_buffer = [0; 18];
+    match card.transmit(&apdu, &mut recv_buffer)? {
+        [data @ .., 0x90, 0x00] if data.len() == 16 => {
+            let mut block = [0; 16];
+            block.copy_from_slice(data);
+            Ok(Some(block))
+        }
+        response => {
+            debug!("READ BINARY page {} refused: {:02X?}", page, response);
+            Ok(None)
+        }
+    }
+}
+
+// Locate the NDEF message TLV and return its value. None when there is none, or when
+// `data` ends before the whole message.
+fn find_ndef_tlv(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+    while pos < data.len() {
+        let tag = data[pos];
+        match tag {
+            TLV_NULL => {
+                pos += 1;
+                continue;
+            }
+            TLV_TERMINATOR => return None,
+            _ => {}
+        }
+        // One length byte, or 0xFF followed by a two-byte length
+        let (len, header) = match *data.get(pos + 1)? {
+            0xFF => (u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize, 4),
+            len => (len as usize, 2),
+        };
+        let value = pos + header;
+        if tag == TLV_NDEF {
+            return data.get(value..value + len);
+        }
+        pos = value + len;
+    }
+    None
+}
+
+// Walk the records of an NDEF message and decode the first text or URI one
+fn parse_message(message: &[u8]) -> Result<Option<String>, AppError> {
+    let malformed = || AppError::Ndef("record runs past the end of the message".to_string());
+    let mut pos = 0;
+    while pos < message.len() {
+        let flags = message[pos];
+        let short_record = flags & 0x10 != 0;
+        let has_id = flags & 0x08 != 0;
+        let tnf = flags & 0x07;
+        let type_len = *message.get(pos + 1).ok_or_else(malformed)? as usize;
+        pos += 2;
+
+        let payload_len = if short_record {
+            *message.get(pos).ok_or_else(malformed)? as usize
+        } else {
+            let bytes = message.get(pos..pos + 4).ok_or_else(malformed)?;
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+        };
+        pos += if short_record { 1 } else { 4 };
+        let id_len = if has_id {
+            let len = *message.get(pos).ok_or_else(malformed)? as usize;
+            pos += 1;
+            len
+        } else {
+            0
+        };
+
+        let record_type = message.get(pos..pos + type_len).ok_or_else(malformed)?;
+        let payload_start = pos + type_len + id_len;
+        let payload = message
+            .get(payload_start..payload_start + payload_len)
+            .ok_or_else(malformed)?;
+        pos = payload_start + payload_len;
+
+        if tnf == TNF_WELL_KNOWN {
+            match record_type {
+                b"T" => return decode_text(payload).map(Some),
+                b"U" => return decode_uri(payload).map(Some),
+                _ => {}
+            }
+        }
+        // Message end flag
+        if flags & 0x40 != 0 {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+// Text record: status byte (bit 7 set for UTF-16, low six bits the language code
+// length), the language code, then the text
+fn decode_text(payload: &[u8]) -> Result<String, AppError> {
+    let status = *payload
+        .first()
+        .ok_or_else(|| AppError::Ndef("empty text record".to_string()))?;
+    let text = payload
+        .get(1 + (status & 0x3F) as usize..)
+        .ok_or_else(|| AppError::Ndef("text record shorter than its language code".to_string()))?;
+    if status & 0x80 != 0 {
+        let units: Vec<u16> = text.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16(&units).map_err(|e| AppError::Ndef(format!("text record: {}", e)))
+    } else {
+        String::from_utf8(text.to_vec()).map_err(|e| AppError::Ndef(format!("text record: {}", e)))
+    }
+}
+
+// URI record: an identifier code abbreviating the scheme, then the rest of the URI
+fn decode_uri(payload: &[u8]) -> Result<String, AppError> {
+    let (&code, rest) = payload
+        .split_first()
+        .ok_or_else(|| AppError::Ndef("empty URI record".to_string()))?;
+    let prefix = URI_PREFIXES.get(code as usize).copied().unwrap_or("");
+    let rest = std::str::from_utf8(rest).map_err(|e| AppError::Ndef(format!("URI record: {}", e)))?;
+    Ok(format!("{}{}", prefix, rest))
+}
@@ -0,0 +1,1651 @@
+use log::{debug, error, info, warn};
+use pcsc::{Card, Context, Error, Protocol, Protocols, ReaderState, Scope, ShareMode, State};
+use slint::{ModelRc, SharedString, VecModel, Weak};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::{ApiClient, Guest, GuestsPostResponse};
+use crate::audit;
+use crate::cache::GuestCache;
+use crate::closed;
+use crate::config::{config, GuestTagSource, UidFormat};
+use crate::i18n::{tr, trf};
+use crate::idle::IdleTracker;
+use crate::lock;
+use crate::metrics::Metrics;
+use crate::ndef::read_ndef;
+use crate::recent;
+use crate::sound;
+use crate::team;
+use crate::token;
+use crate::{cancel_retry, show_error, show_network_error, show_unknown_card, return_to_idle_later, AppError, AppWindow, DiagnosticInfo, GuestChoice};
+
+// Bumped for every guest lookup. A lookup only updates the UI if no newer lookup has
+// started since, so on a slow network the latest card always wins.
+static SCAN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn is_latest_scan(generation: u64) -> bool {
+    SCAN_GENERATION.load(Ordering::SeqCst) == generation
+}
+
+// Result of handling a card, used to drive the reader's LED and buzzer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanOutcome {
+    Success,
+    Error,
+    // The card is not registered for the event: send the guest to the desk
+    NotRegistered,
+    // The API could not be reached: the operator should check the network
+    NetworkError,
+}
+
+// Set while the reader diagnostic screen is up: cards are dumped to it, never looked up
+static DIAGNOSTIC_MODE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_diagnostic_mode(on: bool) {
+    if DIAGNOSTIC_MODE.swap(on, Ordering::SeqCst) != on {
+        info!("Diagnostic mode {}", if on { "on" } else { "off" });
+    }
+}
+
+// ACR122U LED/buzzer APDU: FF 00 40 <LED state> 04 <T1> <T2> <repetitions> <buzzer link>
+// Success blinks green once with a short beep, errors blink red three times with beeps.
+// An unregistered card gives one long red beep; a network error two long beeps
+// alternating red and green, so the operator can tell them apart without looking.
+const FEEDBACK_SUCCESS: [u8; 9] = [0xFF, 0x00, 0x40, 0xAC, 0x04, 0x01, 0x01, 0x01, 0x01];
+const FEEDBACK_ERROR: [u8; 9] = [0xFF, 0x00, 0x40, 0x5C, 0x04, 0x02, 0x02, 0x03, 0x01];
+const FEEDBACK_NOT_REGISTERED: [u8; 9] = [0xFF, 0x00, 0x40, 0x5C, 0x04, 0x0A, 0x01, 0x01, 0x01];
+const FEEDBACK_NETWORK_ERROR: [u8; 9] = [0xFF, 0x00, 0x40, 0xDC, 0x04, 0x06, 0x04, 0x02, 0x01];
+
+// PC/SC pseudo-APDU returning the card UID
+const GET_UID: [u8; 5] = [0xFF, 0xCA, 0x00, 0x00, 0x00];
+
+// ACR122U direct transmit of the PN532 RFConfiguration command, switching the RF field
+// off and back on; clears a card that has wedged the field
+const RF_FIELD_OFF: [u8; 9] = [0xFF, 0x00, 0x00, 0x00, 0x04, 0xD4, 0x32, 0x01, 0x00];
+const RF_FIELD_ON: [u8; 9] = [0xFF, 0x00, 0x00, 0x00, 0x04, 0xD4, 0x32, 0x01, 0x01];
+const RF_OFF_TIME: Duration = Duration::from_millis(100);
+
+// ACR122U escape commands, sent with SCardControl over a direct connection so they work
+// without a card on the reader. Only the documented, non-destructive settings are used:
+// the card-detection beep (FF 00 52) and the PICC operating parameter (FF 00 51).
+const ESCAPE_IOCTL_CODE: u16 = 3500;
+const SET_CARD_BEEP: [u8; 4] = [0xFF, 0x00, 0x52, 0x00];
+const SET_PICC_PARAMETER: [u8; 4] = [0xFF, 0x00, 0x51, 0x00];
+// PICC operating parameter bits: auto polling, auto ATS, 250 ms polling interval; the
+// low five bits (FeliCa 424/212, Topaz, ISO 14443 B and A) are left all enabled
+const PICC_AUTO_POLLING: u8 = 0x80;
+const PICC_AUTO_ATS: u8 = 0x40;
+const PICC_POLL_250MS: u8 = 0x20;
+const PICC_ALL_CARD_TYPES: u8 = 0x1F;
+
+// Default MIFARE Classic transport key, loaded into reader key slot 0 before authenticating
+const MIFARE_DEFAULT_KEY: [u8; 6] = [0xFF; 6];
+
+// Feedback for a lookup that finished after the scanner closed its connection: connect
+// to the reader again to signal. Skipped if the card has been lifted meanwhile.
+fn signal_reader_by_name(reader: &CStr, outcome: ScanOutcome) {
+    if !config().reader_feedback {
+        return;
+    }
+    let Ok(ctx) = Context::establish(Scope::User) else {
+        return;
+    };
+    if let Ok(card) = ctx.connect(reader, ShareMode::Shared, Protocols::ANY) {
+        signal_reader(&card, outcome);
+        let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+    }
+}
+
+// Give the operator physical feedback through the reader, if enabled in config
+pub fn signal_reader(card: &Card, outcome: ScanOutcome) {
+    if !config().reader_feedback {
+        return;
+    }
+    let apdu = match outcome {
+        ScanOutcome::Success => &FEEDBACK_SUCCESS,
+        ScanOutcome::Error => &FEEDBACK_ERROR,
+        ScanOutcome::NotRegistered => &FEEDBACK_NOT_REGISTERED,
+        ScanOutcome::NetworkError => &FEEDBACK_NETWORK_ERROR,
+    };
+    let mut recv_buffer = [0; 16];
+    if let Err(e) = card.transmit(apdu, &mut recv_buffer) {
+        warn!("Failed to signal reader ({:?}): {}", outcome, e);
+    }
+}
+
+// Cycle the reader's RF field after rf_reset_after_failures failed reads in a row, which
+// recovers readers left returning garbage by a card that wedged the field
+fn reset_rf_field(card: &Card, slot: &mut ReaderSlot) {
+    warn!("Reader {}: {} failed reads in a row, resetting the RF field", slot.label, slot.read_failures);
+    slot.read_failures = 0;
+    let mut recv_buffer = [0; 16];
+    let result = card.transmit(&RF_FIELD_OFF, &mut recv_buffer).map(|_| ()).and_then(|_| {
+        thread::sleep(RF_OFF_TIME);
+        card.transmit(&RF_FIELD_ON, &mut recv_buffer).map(|_| ())
+    });
+    match result {
+        Ok(()) => info!("Reader {}: RF field reset", slot.label),
+        Err(e) => warn!("Reader {}: RF field reset failed: {}", slot.label, e),
+    }
+}
+
+// Read a GET UID response: check the trailing 90 00 status word, then that the UID has
+// one of `valid_lengths` bytes (any length when it is empty), and return it written per
+// `format`. Kept free of I/O and config so the reader path, the diagnostic screen and
+// write_stamp all accept the same cards.
+fn parse_uid_response(resp: &[u8], valid_lengths: &[usize], format: UidFormat) -> Result<String, AppError> {
+    let uid = match resp {
+        [uid @ .., 0x90, 0x00] => uid,
+        [.., sw1, sw2] => {
+            return Err(AppError::CardRead(trf(
+                "invalid_response",
+                "Invalid response: {} {}",
+                &[&format!("{:02X}", sw1), &format!("{:02X}", sw2)],
+            )));
+        }
+        _ => return Err(AppError::CardRead(tr("response_too_short", "Invalid response: too short"))),
+    };
+    if uid.is_empty() || !(valid_lengths.is_empty() || valid_lengths.contains(&uid.len())) {
+        return Err(AppError::UidLength(uid.len()));
+    }
+    Ok(format_uid(uid, format))
+}
+
+// Uppercase hex without separators, for diagnostics
+fn hex_string(bytes: &[u8]) -> String {
+    format_uid(bytes, UidFormat::UpperNoSep)
+}
+
+// Format a UID the way the backend expects it. The result is used for display,
+// the guest cache key and the guest_tag, so all three always agree.
+pub fn format_uid(bytes: &[u8], fmt: UidFormat) -> String {
+    let (lowercase, separator) = match fmt {
+        UidFormat::UpperNoSep => (false, ""),
+        UidFormat::LowerNoSep => (true, ""),
+        UidFormat::UpperColon => (false, ":"),
+        UidFormat::LowerColon => (true, ":"),
+    };
+    bytes
+        .iter()
+        .map(|b| if lowercase { format!("{:02x}", b) } else { format!("{:02X}", b) })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+// Best guess at the card family from its ATR. Contactless storage cards follow the
+// PC/SC part 3 layout 3B 8F 80 01 80 4F 0C A0 00 00 03 06 <standard> <name hi> <name lo>.
+fn guess_card_type(atr: &[u8]) -> &'static str {
+    const PCSC_STORAGE_PREFIX: [u8; 12] = [0x3B, 0x8F, 0x80, 0x01, 0x80, 0x4F, 0x0C, 0xA0, 0x00, 0x00, 0x03, 0x06];
+
+    if atr.is_empty() {
+        return "no ATR";
+    }
+    if atr.len() >= 15 && atr.starts_with(&PCSC_STORAGE_PREFIX) {
+        return match (atr[13], atr[14]) {
+            (0x00, 0x01) => "MIFARE Classic 1K",
+            (0x00, 0x02) => "MIFARE Classic 4K",
+            (0x00, 0x03) => "MIFARE Ultralight / NTAG",
+            (0x00, 0x26) => "MIFARE Mini",
+            (0x00, 0x3A) => "MIFARE Ultralight C",
+            (0xF0, 0x04) => "Topaz / Jewel",
+            (0xF0, 0x11) => "FeliCa 212K",
+            (0xF0, 0x12) => "FeliCa 424K",
+            _ => "unknown storage card",
+        };
+    }
+    if atr.len() >= 4 && atr[0] == 0x3B && atr[1] & 0xF0 == 0x80 && atr[2] == 0x80 && atr[3] == 0x01 {
+        // ISO 14443-4 cards (DESFire, payment cards, phones) carry historical bytes instead
+        return "ISO 14443-4 smart card";
+    }
+    "unknown card type"
+}
+
+// Protocols offered by an ATR: each TDi interface byte names one in its low nibble, and
+// an ATR without TD1 implies T=0. Empty when the ATR is missing or truncated.
+fn atr_protocols(atr: &[u8]) -> Protocols {
+    let mut protocols = Protocols::empty();
+    if atr.len() < 2 {
+        return protocols;
+    }
+    let mut pos = 1;
+    let mut has_td = false;
+    while let Some(&format) = atr.get(pos) {
+        // TAi, TBi and TCi come before TDi when present
+        let td_pos = pos + (format >> 4 & 0b0111).count_ones() as usize + 1;
+        if format & 0x80 == 0 {
+            break;
+        }
+        let Some(&td) = atr.get(td_pos) else {
+            return Protocols::empty();
+        };
+        match td & 0x0F {
+            0 => protocols.insert(Protocols::T0),
+            1 => protocols.insert(Protocols::T1),
+            // T=15 carries global parameters rather than a protocol
+            _ => {}
+        }
+        has_td = true;
+        pos = td_pos;
+    }
+    if !has_td {
+        protocols.insert(Protocols::T0);
+    }
+    protocols
+}
+
+fn protocol_names(protocols: Protocols) -> String {
+    let names: Vec<&str> = [(Protocols::T0, "T=0"), (Protocols::T1, "T=1"), (Protocols::RAW, "raw")]
+        .into_iter()
+        .filter(|(p, _)| protocols.contains(*p))
+        .map(|(_, name)| name)
+        .collect();
+    if names.is_empty() { "none".to_string() } else { names.join(", ") }
+}
+
+// Send an APDU and return the SW1 SW2 status word
+fn transmit_status(card: &Card, apdu: &[u8]) -> Result<[u8; 2], AppError> {
+    let mut recv_buffer = [0; 258];
+    let response = card.transmit(apdu, &mut recv_buffer)?;
+    match response {
+        [.., sw1, sw2] => Ok([*sw1, *sw2]),
+        _ => Err(AppError::CardNotWritable("response too short".to_string())),
+    }
+}
+
+// MIFARE Classic sector trailers hold the keys and access bits and must never be overwritten
+fn is_sector_trailer(block: u8) -> bool {
+    if block < 128 { block % 4 == 3 } else { block % 16 == 15 }
+}
+
+// Write one 16-byte block of a MIFARE Classic card: load the default key, authenticate
+// the block with key A, then FF D6 update binary. Other card families, block 0 and
+// sector trailers are refused. A rejected key is reported as CardAuth.
+pub fn write_card_block(card: &Card, block: u8, data: &[u8]) -> Result<(), AppError> {
+    let atr = card.status2_owned().map(|s| s.atr().to_vec()).unwrap_or_default();
+    let card_type = guess_card_type(&atr);
+    if !card_type.starts_with("MIFARE Classic") {
+        return Err(AppError::CardNotWritable(card_type.to_string()));
+    }
+    if block == 0 || is_sector_trailer(block) {
+        return Err(AppError::CardNotWritable(format!("block {} is reserved", block)));
+    }
+    if data.len() != 16 {
+        return Err(AppError::CardNotWritable(format!("block data must be 16 bytes, got {}", data.len())));
+    }
+
+    let mut load_key = vec![0xFF, 0x82, 0x00, 0x00, 0x06];
+    load_key.extend_from_slice(&MIFARE_DEFAULT_KEY);
+    if transmit_status(card, &load_key)? != [0x90, 0x00] {
+        return Err(AppError::CardAuth(block));
+    }
+
+    let authenticate = [0xFF, 0x86, 0x00, 0x00, 0x05, 0x01, 0x00, block, 0x60, 0x00];
+    if transmit_status(card, &authenticate)? != [0x90, 0x00] {
+        return Err(AppError::CardAuth(block));
+    }
+
+    let mut write = vec![0xFF, 0xD6, 0x00, block, 0x10];
+    write.extend_from_slice(data);
+    match transmit_status(card, &write)? {
+        [0x90, 0x00] => Ok(()),
+        [sw1, sw2] => Err(AppError::CardNotWritable(format!("write answered {:02X} {:02X}", sw1, sw2))),
+    }
+}
+
+// Stamp block contents: "RPIUI" marker, then the checkpoint id (big endian), zero padded
+fn stamp_data(checkpoint_id: i32) -> [u8; 16] {
+    let mut data = [0u8; 16];
+    data[..5].copy_from_slice(b"RPIUI");
+    data[5..9].copy_from_slice(&checkpoint_id.to_be_bytes());
+    data
+}
+
+// Write the "visited" stamp to the card with UID `guest_tag`, which must still be on
+// one of the readers. Uses its own PC/SC context, separate from the scanning thread.
+pub fn write_stamp(guest_tag: &str, checkpoint_id: i32) -> Result<(), AppError> {
+    let ctx = Context::establish(Scope::User)?;
+    for slot in find_readers(&ctx)? {
+        let card = match ctx.connect(&slot.name, ShareMode::Shared, Protocols::ANY) {
+            Ok(card) => card,
+            Err(Error::NoSmartcard) | Err(Error::RemovedCard) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut recv_buffer = [0; 256];
+        let response = card.transmit(&GET_UID, &mut recv_buffer)?;
+        let Ok(uid) = parse_uid_response(response, config().accepted_uid_lengths(), config().uid_format) else {
+            continue;
+        };
+        if card_guest_tag(&card, &uid) == guest_tag {
+            let result = write_card_block(&card, config().stamp_block, &stamp_data(checkpoint_id));
+            let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+            return result;
+        }
+    }
+    Err(AppError::CardNotWritable(format!("card {} is no longer on the reader", guest_tag)))
+}
+
+// The guest_tag for the card on the reader: its UID, or with guest_tag_source = "ndef"
+// its NDEF text/URI record when it has one
+fn card_guest_tag(card: &Card, uid_str: &str) -> String {
+    if config().guest_tag_source != GuestTagSource::Ndef {
+        return uid_str.to_string();
+    }
+    match read_ndef(card) {
+        Ok(Some(value)) if !value.trim().is_empty() => value.trim().to_string(),
+        Ok(_) => {
+            debug!("Card {} has no NDEF text or URI record, using its UID", uid_str);
+            uid_str.to_string()
+        }
+        Err(e) => {
+            warn!("Cannot read NDEF from card {}, using its UID: {}", uid_str, e);
+            uid_str.to_string()
+        }
+    }
+}
+
+// Per-reader state kept between status changes
+struct ReaderSlot {
+    name: CString,
+    label: String,
+    last_uid: String,
+    card_present: bool,
+    // When the card was last seen leaving, for removal_debounce
+    lifted_at: Option<Instant>,
+    // Protocol asked for on connect: ANY until a card has shown which one this reader negotiates
+    protocols: Protocols,
+    // UID reads failed in a row, for rf_reset_after_failures
+    read_failures: u32,
+    // The reader went away; it is dropped while the other readers keep being polled
+    lost: bool,
+}
+
+impl ReaderSlot {
+    fn new(name: CString) -> Self {
+        let label = name.to_string_lossy().into_owned();
+        ReaderSlot {
+            name,
+            label,
+            last_uid: String::new(),
+            card_present: false,
+            lifted_at: None,
+            protocols: Protocols::ANY,
+            read_failures: 0,
+            lost: false,
+        }
+    }
+
+    // Connect with the protocol learned earlier, falling back to ANY if the card on the
+    // reader now needs a different one
+    fn connect(&mut self, ctx: &Context) -> Result<Card, Error> {
+        match ctx.connect(&self.name, ShareMode::Shared, self.protocols) {
+            Err(Error::ProtoMismatch) if self.protocols != Protocols::ANY => {
+                warn!("Reader {}: card does not accept {}, trying any protocol", self.label, protocol_names(self.protocols));
+                self.protocols = Protocols::ANY;
+                ctx.connect(&self.name, ShareMode::Shared, Protocols::ANY)
+            }
+            result => result,
+        }
+    }
+
+    // Log the protocols offered by the card's ATR and the one negotiated, and ask for the
+    // negotiated one from now on. Only does work until the reader's protocol is known.
+    fn learn_protocol(&mut self, card: &Card) {
+        if self.protocols != Protocols::ANY {
+            return;
+        }
+        let status = match card.status2_owned() {
+            Ok(status) => status,
+            Err(e) => {
+                debug!("Reader {}: cannot read card status: {}", self.label, e);
+                return;
+            }
+        };
+        let offered = atr_protocols(status.atr());
+        let active = match status.protocol2() {
+            Some(Protocol::T0) => Protocols::T0,
+            Some(Protocol::T1) => Protocols::T1,
+            Some(Protocol::RAW) => Protocols::RAW,
+            None => Protocols::empty(),
+        };
+        if active.is_empty() && offered.is_empty() {
+            warn!(
+                "Reader {} reports no usable protocol (ATR {}), reads may fail",
+                self.label,
+                hex_string(status.atr())
+            );
+            return;
+        }
+        info!(
+            "Reader {}: ATR {} offers {}, negotiated {}",
+            self.label,
+            hex_string(status.atr()),
+            protocol_names(offered),
+            protocol_names(active)
+        );
+        if !active.is_empty() {
+            self.protocols = active;
+        }
+    }
+}
+
+// Startup probe: learn the protocol from a card already on the reader, if any
+fn probe_reader(ctx: &Context, slot: &mut ReaderSlot) {
+    match ctx.connect(&slot.name, ShareMode::Shared, Protocols::ANY) {
+        Ok(card) => {
+            slot.learn_protocol(&card);
+            let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+        }
+        Err(Error::NoSmartcard) | Err(Error::RemovedCard) => {
+            debug!("Reader {}: no card yet, protocol is learned from the first one", slot.label);
+        }
+        Err(e) => warn!("Reader {}: probe failed: {}", slot.label, e),
+    }
+}
+
+// Apply reader_card_beep, reader_auto_polling and reader_poll_interval_ms to a newly found
+// reader. Failures are logged and the reader is used as it is.
+fn configure_reader(ctx: &Context, slot: &ReaderSlot) {
+    let cfg = config();
+    let mut commands = Vec::new();
+    if let Some(beep) = cfg.reader_card_beep {
+        commands.push(("card beep", escape_command(SET_CARD_BEEP, if beep { 0xFF } else { 0x00 })));
+    }
+    if cfg.reader_auto_polling.is_some() || cfg.reader_poll_interval_ms.is_some() {
+        let mut parameter = PICC_AUTO_ATS | PICC_ALL_CARD_TYPES;
+        if cfg.reader_auto_polling.unwrap_or(true) {
+            parameter |= PICC_AUTO_POLLING;
+        }
+        if cfg.reader_poll_interval_ms.unwrap_or(250) == 250 {
+            parameter |= PICC_POLL_250MS;
+        }
+        commands.push(("PICC operating parameter", escape_command(SET_PICC_PARAMETER, parameter)));
+    }
+    if commands.is_empty() {
+        return;
+    }
+
+    let card = match ctx.connect(&slot.name, ShareMode::Direct, Protocols::UNDEFINED) {
+        Ok(card) => card,
+        Err(e) => {
+            warn!("Reader {}: direct connection for escape commands failed: {}", slot.label, e);
+            return;
+        }
+    };
+    for (setting, command) in commands {
+        let mut recv_buffer = [0; 16];
+        match card.control(pcsc::ctl_code(ESCAPE_IOCTL_CODE.into()), &command, &mut recv_buffer) {
+            Ok([0x90, value, ..]) => info!("Reader {}: {} set to {:02X}", slot.label, setting, value),
+            Ok(response) => warn!("Reader {}: setting {} answered {}", slot.label, setting, hex_string(response)),
+            Err(e) => warn!("Reader {}: setting {} failed: {}", slot.label, setting, e),
+        }
+    }
+    let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+}
+
+fn escape_command(header: [u8; 4], value: u8) -> [u8; 5] {
+    [header[0], header[1], header[2], value, 0x00]
+}
+
+// Bounds how many lookups run at once on worker threads
+#[derive(Default)]
+struct InFlight {
+    count: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl InFlight {
+    fn acquire(&self, max: usize) {
+        let mut count = self.count.lock().unwrap();
+        while *count >= max {
+            count = self.freed.wait(count).unwrap();
+        }
+        *count += 1;
+    }
+
+    fn release(&self) {
+        *self.count.lock().unwrap() -= 1;
+        self.freed.notify_one();
+    }
+}
+
+// Every reader PC/SC knows about, matched or not
+fn all_readers(ctx: &Context) -> Result<Vec<CString>, Error> {
+    let mut readers_buffer = [0; 2048];
+    let readers = ctx.list_readers(&mut readers_buffer)?;
+    Ok(readers.map(|r| r.to_owned()).collect())
+}
+
+// Collect every reader whose name contains one of the configured reader_names
+fn find_readers(ctx: &Context) -> Result<Vec<ReaderSlot>, Error> {
+    Ok(all_readers(ctx)?
+        .into_iter()
+        .filter(|r| {
+            let name = r.to_string_lossy();
+            config().reader_names.iter().any(|wanted| name.contains(wanted.as_str()))
+        })
+        .map(ReaderSlot::new)
+        .collect())
+}
+
+// --list-readers: print every reader name, with the ATR of the card on it if any, so a
+// technician can pick the value for --reader or reader_names
+pub fn print_readers() -> Result<(), AppError> {
+    let ctx = Context::establish(Scope::User)?;
+    let readers = match all_readers(&ctx) {
+        Ok(readers) => readers,
+        Err(Error::NoReadersAvailable) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if readers.is_empty() {
+        println!("No readers found");
+    }
+    for name in readers {
+        let atr = match ctx.connect(&name, ShareMode::Shared, Protocols::ANY) {
+            Ok(card) => {
+                let atr = card
+                    .status2_owned()
+                    .map(|status| format!("{}, protocols {}", hex_string(status.atr()), protocol_names(atr_protocols(status.atr()))));
+                let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                match atr {
+                    Ok(atr) => format!("card ATR {}", atr),
+                    Err(e) => format!("card present, ATR unreadable: {}", e),
+                }
+            }
+            Err(Error::NoSmartcard) | Err(Error::RemovedCard) => "no card".to_string(),
+            Err(e) => format!("unavailable: {}", e),
+        };
+        println!("{}  ({})", name.to_string_lossy(), atr);
+    }
+    Ok(())
+}
+
+// NFC scanning thread body: waits for card events on every matched reader until `shutdown` is set.
+// If the reader or the PC/SC service goes away, the context is rebuilt once it comes back.
+fn run_scanner(
+    api: Arc<dyn ApiClient>,
+    idle: Arc<IdleTracker>,
+    metrics: Arc<Metrics>,
+    ui_handle: Weak<AppWindow>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut scanner = Scanner::new(api, idle, metrics, ui_handle);
+
+    let mut waiting_message = tr("reader_not_found", "No ACR122U reader found, waiting for it to be connected...");
+    while let Some((ctx, mut readers)) = wait_for_readers(&scanner.ui_handle, &shutdown, &waiting_message) {
+        scanner.scan(&ctx, &mut readers, &shutdown);
+        waiting_message = tr("reader_disconnected", "Reader disconnected, waiting...");
+    }
+    info!("NFC scanner stopped");
+}
+
+// Watchdog around run_scanner: a panic in the scanning code is logged and the
+// scanner restarted, up to scanner_max_restarts times, so an unattended kiosk
+// keeps reading cards through transient faults.
+pub fn supervise_scanner(
+    api: Arc<dyn ApiClient>,
+    idle: Arc<IdleTracker>,
+    metrics: Arc<Metrics>,
+    ui_handle: Weak<AppWindow>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut restarts = 0;
+    loop {
+        let run = {
+            let (api, idle, metrics) = (api.clone(), idle.clone(), metrics.clone());
+            let (ui_handle, shutdown) = (ui_handle.clone(), shutdown.clone());
+            move || run_scanner(api, idle, metrics, ui_handle, shutdown)
+        };
+        let Err(payload) = panic::catch_unwind(AssertUnwindSafe(run)) else {
+            return;
+        };
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        error!("NFC scanner panicked: {}", reason);
+
+        if shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        if restarts >= config().scanner_max_restarts {
+            error!("NFC scanner panicked {} times, giving up", restarts + 1);
+            show_error(&ui_handle, &tr("reader_stopped", "Card reader stopped after repeated failures, restart the kiosk"));
+            return;
+        }
+        restarts += 1;
+        warn!("Restarting NFC scanner ({} of {})", restarts, config().scanner_max_restarts);
+        show_error(&ui_handle, &tr("reader_restarting", "Card reader restarting..."));
+        thread::sleep(config().reader_reconnect_delay);
+    }
+}
+
+// --simulate thread body: each line typed on stdin is treated as a scanned UID
+// (hex, with or without ':' separators) and goes through the same guest lookup as a card.
+pub fn run_simulator(api: Arc<dyn ApiClient>, idle: Arc<IdleTracker>, metrics: Arc<Metrics>, ui_handle: Weak<AppWindow>) {
+    let scanner = Scanner::new(api, idle, metrics, ui_handle);
+    info!("Simulation mode: type a card UID in hex and press Enter");
+
+    for line in std::io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to read stdin: {}", e);
+                break;
+            }
+        };
+        let hex: String = line.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+        if hex.is_empty() {
+            continue;
+        }
+        let bytes = match parse_hex(&hex) {
+            Some(bytes) => bytes,
+            None => {
+                warn!("Not a hex UID: {:?}", line);
+                continue;
+            }
+        };
+
+        let uid_str = format_uid(&bytes, config().uid_format);
+        if lock::is_locked() {
+            info!("Kiosk locked, ignoring simulated card {}", uid_str);
+            continue;
+        }
+        if closed::is_closed() {
+            info!("Station closed, ignoring simulated card {}", uid_str);
+            sound::play(ScanOutcome::Error);
+            continue;
+        }
+        if token::scans_paused() {
+            info!("Access token expired, ignoring simulated card {}", uid_str);
+            continue;
+        }
+        info!("Simulated card {}", uid_str);
+        match scanner.lookup.run("simulated", &uid_str) {
+            Err(AppError::TokenExpired) => token::expired(&scanner.ui_handle),
+            Err(e) => show_error(&scanner.ui_handle, &trf("simulated_scan_failed", "Simulated scan failed: {}", &[&e])),
+            Ok(outcome) => {
+                debug!("Simulated scan outcome: {:?}", outcome);
+                sound::play(outcome);
+            }
+        }
+    }
+    info!("Simulation input closed");
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Names of the connected readers matching reader_names, for diagnostics
+pub fn list_matching_readers() -> Result<Vec<String>, Error> {
+    let ctx = Context::establish(Scope::User)?;
+    match find_readers(&ctx) {
+        Ok(readers) => Ok(readers.into_iter().map(|slot| slot.label).collect()),
+        Err(Error::NoReadersAvailable) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Errors meaning the reader or the PC/SC service is gone, as opposed to a bad card
+fn is_reader_lost(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::ReaderUnavailable
+            | Error::UnknownReader
+            | Error::NoReadersAvailable
+            | Error::NoService
+            | Error::ServiceStopped
+            | Error::InvalidHandle
+    )
+}
+
+// Establish a context and locate the readers, retrying with backoff until one shows up.
+// The "reader disconnected" screen stays up meanwhile, with `message` while no reader is
+// attached; PC/SC failures get their own message.
+// Returns None if shutdown is requested first or pcsc_max_attempts is exhausted.
+fn wait_for_readers(
+    ui_handle: &Weak<AppWindow>,
+    shutdown: &AtomicBool,
+    message: &str,
+) -> Option<(Context, Vec<ReaderSlot>)> {
+    let mut delay = config().reader_reconnect_delay;
+    let mut pcsc_failures = 0;
+    let mut shown: Option<String> = None;
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let status = match Context::establish(Scope::User) {
+            Ok(ctx) => match find_readers(&ctx) {
+                Ok(mut readers) if !readers.is_empty() => {
+                    for slot in &mut readers {
+                        start_reader(&ctx, slot);
+                    }
+                    if shown.is_some() {
+                        info!("Reader available again, resuming");
+                        show_reader_status(ui_handle, "");
+                    }
+                    return Some((ctx, readers));
+                }
+                Ok(_) | Err(Error::NoReadersAvailable) => message.to_string(),
+                Err(e) => trf("list_readers_failed", "Failed to list readers ({}), retrying...", &[&e]),
+            },
+            Err(e) => {
+                pcsc_failures += 1;
+                let max_attempts = config().pcsc_max_attempts;
+                if max_attempts > 0 && pcsc_failures >= max_attempts {
+                    error!("Giving up on PC/SC after {} attempts: {}", pcsc_failures, e);
+                    show_reader_status(ui_handle, &trf("pcsc_gave_up", "NFC disabled: could not connect to PC/SC ({})", &[&e]));
+                    return None;
+                }
+                if e == Error::NoService {
+                    tr("pcscd_not_running", "PC/SC daemon (pcscd) is not running, waiting for it to start...")
+                } else {
+                    trf("pcsc_establish_failed", "Failed to establish PC/SC context ({}), retrying...", &[&e])
+                }
+            }
+        };
+
+        debug!("{} (next attempt in {:?})", status, delay);
+        if shown.as_deref() != Some(status.as_str()) {
+            warn!("{}", status);
+            show_reader_status(ui_handle, &status);
+            shown = Some(status);
+        }
+
+        let resume_at = Instant::now() + delay;
+        while Instant::now() < resume_at && !shutdown.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(200));
+        }
+        delay = (delay * 2).min(config().reader_reconnect_max_delay);
+    }
+    None
+}
+
+fn start_reader(ctx: &Context, slot: &mut ReaderSlot) {
+    info!("Using reader: {}", slot.label);
+    configure_reader(ctx, slot);
+    probe_reader(ctx, slot);
+}
+
+// Take on matching readers that are not polled yet, e.g. one plugged back in.
+// Returns true if any was added.
+fn add_new_readers(ctx: &Context, readers: &mut Vec<ReaderSlot>) -> bool {
+    let found = match find_readers(ctx) {
+        Ok(found) => found,
+        Err(Error::NoReadersAvailable) => return false,
+        Err(e) => {
+            warn!("Failed to list readers: {}", e);
+            return false;
+        }
+    };
+    let before = readers.len();
+    for mut slot in found {
+        if readers.iter().any(|known| known.name == slot.name) {
+            continue;
+        }
+        start_reader(ctx, &mut slot);
+        readers.push(slot);
+    }
+    readers.len() > before
+}
+
+// Stop polling the readers marked lost. Returns true if any was dropped.
+fn drop_lost_readers(readers: &mut Vec<ReaderSlot>) -> bool {
+    let before = readers.len();
+    readers.retain(|slot| {
+        if slot.lost {
+            warn!("Reader {} is no longer available", slot.label);
+        }
+        !slot.lost
+    });
+    readers.len() < before
+}
+
+// Put up the "reader disconnected" screen with `status` as the detail, or take it
+// down with an empty status
+fn show_reader_status(ui_handle: &Weak<AppWindow>, status: &str) {
+    let weak = ui_handle.clone();
+    let status = SharedString::from(status);
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            ui.set_reader_status(status);
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+// Fresh status array for get_status_change; UNAWARE makes the first call report the current
+// state. With `pnp`, the PnP notification entry goes last, in that state, to report
+// readers being plugged in.
+fn unaware_states(readers: &[ReaderSlot], pnp: Option<State>) -> Vec<ReaderState> {
+    let mut states: Vec<ReaderState> = readers
+        .iter()
+        .map(|slot| ReaderState::new(slot.name.clone(), State::UNAWARE))
+        .collect();
+    if let Some(pnp) = pnp {
+        states.push(ReaderState::new(pcsc::PNP_NOTIFICATION(), pnp));
+    }
+    states
+}
+
+// State shared by every reader polled from the scanning thread
+struct Scanner {
+    idle: Arc<IdleTracker>,
+    ui_handle: Weak<AppWindow>,
+    lookup: Arc<Lookup>,
+    // Lookups running on worker threads
+    in_flight: Arc<InFlight>,
+    // Whether this thread has seen token::scans_paused, so it knows when scans resume
+    paused: bool,
+    // Wait after connecting before GET UID, learned from recent reads
+    stabilize_delay: Duration,
+    // When each UID was last looked up, for the rescan_cooldown window
+    recent_scans: HashMap<String, Instant>,
+}
+
+impl Scanner {
+    fn new(api: Arc<dyn ApiClient>, idle: Arc<IdleTracker>, metrics: Arc<Metrics>, ui_handle: Weak<AppWindow>) -> Self {
+        let lookup = Arc::new(Lookup {
+            api,
+            idle: idle.clone(),
+            metrics,
+            ui_handle: ui_handle.clone(),
+            guest_cache: Mutex::new(GuestCache::new(config().guest_cache_ttl, config().guest_cache_size)),
+        });
+        Scanner {
+            idle,
+            ui_handle,
+            lookup,
+            in_flight: Arc::new(InFlight::default()),
+            paused: false,
+            stabilize_delay: config().stabilize_delay,
+            recent_scans: HashMap::new(),
+        }
+    }
+
+    // Handle card events on one context until shutdown, the PC/SC service goes away or the
+    // last reader is gone. A reader that disappears is dropped while the others keep being
+    // polled; readers plugged in later are taken on.
+    fn scan(&mut self, ctx: &Context, readers: &mut Vec<ReaderSlot>, shutdown: &AtomicBool) {
+        // Last state of the PnP notification entry; None where PC/SC does not offer it
+        let mut pnp = Some(State::UNAWARE);
+        let mut states = unaware_states(readers, pnp);
+
+        while !shutdown.load(Ordering::SeqCst) {
+            // Paused by an expired token, here, on a worker or in the heartbeat, until
+            // a token reload resumes scans
+            if token::scans_paused() {
+                if !self.paused {
+                    self.paused = true;
+                    // Cards tapped meanwhile are looked up again once the token is reloaded
+                    self.recent_scans.clear();
+                    for slot in readers.iter_mut() {
+                        slot.last_uid.clear();
+                    }
+                }
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+            if self.paused {
+                self.paused = false;
+                // Forget what we knew so cards left on the reader are read again
+                states = unaware_states(readers, pnp);
+                for slot in readers.iter_mut() {
+                    slot.card_present = false;
+                }
+            }
+
+            // Block until a card is inserted or removed; the timeout only bounds how long shutdown waits
+            match ctx.get_status_change(config().scan_interval, &mut states) {
+                Ok(()) => {}
+                Err(Error::Timeout) => {
+                    // Without PnP notifications, look for new readers on every timeout instead
+                    if pnp.is_none() && add_new_readers(ctx, readers) {
+                        states = unaware_states(readers, pnp);
+                    }
+                    continue;
+                }
+                // One of the readers went away before the call; keep the ones still listed
+                Err(Error::UnknownReader | Error::ReaderUnavailable) => {
+                    let listed = all_readers(ctx).unwrap_or_default();
+                    for slot in readers.iter_mut().filter(|slot| !listed.contains(&slot.name)) {
+                        slot.lost = true;
+                    }
+                    if !drop_lost_readers(readers) {
+                        // Every reader is still there, so it was the PnP entry that was refused
+                        if pnp.take().is_none() {
+                            warn!("Reader status failed with every reader still listed, reconnecting");
+                            return;
+                        }
+                        info!("PC/SC has no reader notifications, checking for new readers every scan_interval");
+                    }
+                    if readers.is_empty() {
+                        return;
+                    }
+                    states = unaware_states(readers, pnp);
+                    continue;
+                }
+                Err(e) if is_reader_lost(&e) => {
+                    warn!("PC/SC lost: {}", e);
+                    return;
+                }
+                Err(e) => {
+                    show_error(&self.ui_handle, &trf("reader_status_error", "Reader status error: {}", &[&e]));
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            }
+
+            for (slot, state) in readers.iter_mut().zip(states.iter_mut()) {
+                let event = state.event_state();
+                state.sync_current_state();
+
+                if event.intersects(State::UNKNOWN | State::UNAVAILABLE) {
+                    slot.lost = true;
+                    continue;
+                }
+
+                let present = event.contains(State::PRESENT) && !event.contains(State::MUTE);
+                if present == slot.card_present {
+                    continue;
+                }
+                slot.card_present = present;
+
+                if !present {
+                    slot.lifted_at = Some(Instant::now());
+                    // A failed submission is only retried while its guest is still here
+                    cancel_retry(&self.ui_handle);
+                    continue;
+                }
+                // The card counts as lifted (and the same UID may be scanned again) only
+                // if it stayed away for removal_debounce; a brief loss of contact while
+                // held on the reader is treated as the same tap
+                if let Some(lifted_at) = slot.lifted_at.take()
+                    && lifted_at.elapsed() >= config().removal_debounce
+                {
+                    slot.last_uid.clear();
+                }
+                if shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                match self.read_reader(ctx, slot) {
+                    Err(AppError::TokenExpired) => {
+                        token::expired(&self.ui_handle);
+                        break;
+                    }
+                    // The reader itself is gone; the service going away ends this context
+                    Err(AppError::Pcsc(e @ (Error::ReaderUnavailable | Error::UnknownReader))) => {
+                        warn!("Reader {} lost: {}", slot.label, e);
+                        slot.lost = true;
+                        continue;
+                    }
+                    Err(AppError::Pcsc(e)) if is_reader_lost(&e) => {
+                        warn!("PC/SC lost on reader {}: {}", slot.label, e);
+                        return;
+                    }
+                    _ => {}
+                }
+                // Optional settle time before the next card event is handled
+                if !config().post_read_delay.is_zero() {
+                    thread::sleep(config().post_read_delay);
+                }
+            }
+
+            // The PnP entry is the last one; it changes when a reader is plugged in or removed
+            let mut changed = drop_lost_readers(readers);
+            if let Some(pnp_state) = pnp.and(states.last_mut()) {
+                let event = pnp_state.event_state();
+                pnp_state.sync_current_state();
+                if event.contains(State::UNKNOWN) {
+                    info!("PC/SC has no reader notifications, checking for new readers every scan_interval");
+                    pnp = None;
+                    changed = true;
+                } else if event.contains(State::CHANGED) {
+                    pnp = Some(pnp_state.current_state());
+                    changed |= add_new_readers(ctx, readers);
+                }
+            }
+            if readers.is_empty() {
+                return;
+            }
+            if changed {
+                states = unaware_states(readers, pnp);
+            }
+        }
+    }
+
+    // Connect to a reader that just reported a card and run the guest lookup.
+    // Only errors that should stop scanning altogether (an expired token, a lost reader) are returned.
+    fn read_reader(&mut self, ctx: &Context, slot: &mut ReaderSlot) -> Result<(), AppError> {
+        match slot.connect(ctx) {
+            Ok(card) => {
+                slot.learn_protocol(&card);
+                thread::sleep(self.stabilize_delay);
+                // Nothing is read while the operator lock is on; the guest taps again once unlocked
+                if lock::is_locked() {
+                    debug!("Kiosk locked, ignoring card on {}", slot.label);
+                    let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                    return Ok(());
+                }
+                if DIAGNOSTIC_MODE.load(Ordering::SeqCst) {
+                    self.diagnose(&card, slot);
+                    let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                    return Ok(());
+                }
+                // A closed station acknowledges the tap with an error beep but scores nothing
+                if closed::is_closed() {
+                    info!("Station closed, ignoring card on {}", slot.label);
+                    signal_reader(&card, ScanOutcome::Error);
+                    sound::play(ScanOutcome::Error);
+                    let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                    return Ok(());
+                }
+                let result = self.read_card(&card, slot);
+                let outcome = match &result {
+                    Ok(outcome) => *outcome,
+                    Err(_) => Some(ScanOutcome::Error),
+                };
+                if let Some(outcome) = outcome {
+                    signal_reader(&card, outcome);
+                    sound::play(outcome);
+                }
+                let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                result.map(|_| ())
+            }
+            // Card was lifted again before we could connect
+            Err(Error::NoSmartcard) | Err(Error::RemovedCard) => Ok(()),
+            Err(e) if is_reader_lost(&e) => Err(AppError::Pcsc(e)),
+            Err(e) => {
+                show_error(&self.ui_handle, &trf("connect_error", "Connect error on {}: {}", &[&slot.label, &e]));
+                Ok(())
+            }
+        }
+    }
+
+    // Run a lookup on a worker thread, first waiting while lookup_workers are already busy
+    fn dispatch(&self, slot: &ReaderSlot, guest_tag: String) {
+        self.in_flight.acquire(config().lookup_workers);
+        let lookup = self.lookup.clone();
+        let in_flight = self.in_flight.clone();
+        let ui_handle = self.ui_handle.clone();
+        let reader = slot.name.clone();
+        let label = slot.label.clone();
+        thread::spawn(move || {
+            let outcome = match lookup.run(&label, &guest_tag) {
+                Ok(outcome) => outcome,
+                Err(AppError::TokenExpired) => {
+                    token::expired(&ui_handle);
+                    ScanOutcome::Error
+                }
+                Err(e) => {
+                    warn!("Lookup for {} on {} failed: {}", guest_tag, label, e);
+                    ScanOutcome::Error
+                }
+            };
+            in_flight.release();
+            sound::play(outcome);
+            signal_reader_by_name(&reader, outcome);
+        });
+    }
+
+    // Diagnostic mode: show the ATR and the raw GET UID exchange for the card, and the UID
+    // as it would be sent, without any API call
+    fn diagnose(&self, card: &Card, slot: &ReaderSlot) {
+        self.idle.touch();
+        let atr = card.status2_owned().map(|s| s.atr().to_vec()).unwrap_or_default();
+        let mut recv_buffer = [0; 256];
+        let (response, uid_length, uid) = match card.transmit(&GET_UID, &mut recv_buffer) {
+            Ok(response) => (
+                hex_string(response),
+                response.len().saturating_sub(2),
+                parse_uid_response(response, config().accepted_uid_lengths(), config().uid_format),
+            ),
+            Err(e) => (format!("transmit failed: {}", e), 0, Err(AppError::Pcsc(e))),
+        };
+        let verdict = match &uid {
+            Ok(_) => "OK: reader fine, any failure is on the API side".to_string(),
+            Err(AppError::UidLength(len)) => {
+                format!("length {} not in valid_uid_lengths {:?}", len, config().valid_uid_lengths)
+            }
+            Err(_) => "no UID: reader or card problem".to_string(),
+        };
+        let uid_length = match &uid {
+            Ok(_) | Err(AppError::UidLength(_)) => SharedString::from(uid_length.to_string()),
+            Err(_) => SharedString::new(),
+        };
+        let info = DiagnosticInfo {
+            reader: SharedString::from(slot.label.as_str()),
+            atr: SharedString::from(hex_string(&atr)),
+            card_type: SharedString::from(format!("{} ({})", guess_card_type(&atr), protocol_names(slot.protocols))),
+            response: SharedString::from(response),
+            uid_length,
+            uid: uid.map(SharedString::from).unwrap_or_default(),
+            verdict: SharedString::from(verdict),
+        };
+        info!(
+            "Diagnostic on {}: ATR {}, GET UID -> {}, {}",
+            info.reader, info.atr, info.response, info.verdict
+        );
+        let weak = self.ui_handle.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(ui) = weak.upgrade() {
+                ui.set_diagnostic(info);
+                ui.set_current_screen(SharedString::from("diagnostic"));
+            }
+        }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+    }
+
+    // Send GET UID, retrying up to uid_read_retries times while the card is still settling.
+    // The wait that finally worked feeds into the stabilize delay used for the next card.
+    // A UID of the wrong length is the card itself, not a misread, and is not retried.
+    fn read_uid(&mut self, card: &Card) -> Result<String, AppError> {
+        let mut recv_buffer = [0; 256];
+        let mut waited = self.stabilize_delay;
+        let mut attempt = 0;
+
+        loop {
+            let result = match card.transmit(&GET_UID, &mut recv_buffer) {
+                Ok(response) => parse_uid_response(response, config().accepted_uid_lengths(), config().uid_format),
+                Err(e) => Err(AppError::CardRead(trf("read_failed", "Failed to read card: {}", &[&e]))),
+            };
+
+            match result {
+                Ok(uid) => {
+                    self.learn_stabilize_delay(waited, attempt);
+                    return Ok(uid);
+                }
+                Err(e @ AppError::CardRead(_)) if attempt < config().uid_read_retries => {
+                    attempt += 1;
+                    debug!("{}, retrying UID read (attempt {})", e, attempt);
+                    thread::sleep(config().uid_retry_delay);
+                    waited += config().uid_retry_delay;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Move the stabilize delay towards what the last card needed: up when retries
+    // were required, slowly back down towards the configured value when reads succeed first time
+    fn learn_stabilize_delay(&mut self, waited: Duration, retries: u32) {
+        let target = if retries > 0 { waited } else { config().stabilize_delay };
+        let learned = (self.stabilize_delay * 3 + target) / 4;
+        // min then max rather than clamp, which would panic on a max below the base delay
+        let learned = learned.min(config().stabilize_delay_max).max(config().stabilize_delay);
+        if learned != self.stabilize_delay {
+            debug!("Stabilize delay adjusted to {:?}", learned);
+            self.stabilize_delay = learned;
+        }
+    }
+
+    // True if `uid` was looked up less than rescan_cooldown ago; otherwise records it now.
+    // Unlike last_uid this also covers two cards tapped alternately, or the same card on another reader.
+    fn in_cooldown(&mut self, uid: &str) -> bool {
+        let cooldown = config().rescan_cooldown;
+        if let Some(at) = self.recent_scans.get(uid)
+            && at.elapsed() < cooldown
+        {
+            return true;
+        }
+        self.recent_scans.retain(|_, at| at.elapsed() < cooldown);
+        self.recent_scans.insert(uid.to_string(), Instant::now());
+        false
+    }
+
+    // Read the card UID and look up the guest; returns Ok(None) when nothing new happened
+    fn read_card(&mut self, card: &Card, slot: &mut ReaderSlot) -> Result<Option<ScanOutcome>, AppError> {
+        let uid_str = match self.read_uid(card) {
+            Ok(uid) => {
+                slot.read_failures = 0;
+                uid
+            }
+            Err(AppError::UidLength(len)) => {
+                slot.read_failures = 0;
+                // Logged at warn with the ATR so admins know which length to add to
+                // valid_uid_lengths, and can tell an unsupported card family from a misread
+                let atr = card.status2_owned().map(|s| s.atr().to_vec()).unwrap_or_default();
+                warn!(
+                    "Rejected UID of length {} on {} (valid_uid_lengths = {:?}): ATR {} ({})",
+                    len,
+                    slot.label,
+                    config().valid_uid_lengths,
+                    hex_string(&atr),
+                    guess_card_type(&atr)
+                );
+                show_error(&self.ui_handle, &tr("card_not_supported", "Card not supported, please use an event wristband"));
+                return Ok(Some(ScanOutcome::Error));
+            }
+            Err(e) => {
+                show_error(&self.ui_handle, &e.to_string());
+                slot.read_failures += 1;
+                let threshold = config().rf_reset_after_failures;
+                if threshold > 0 && slot.read_failures >= threshold {
+                    reset_rf_field(card, slot);
+                }
+                return Ok(Some(ScanOutcome::Error));
+            }
+        };
+
+        if uid_str == slot.last_uid {
+            return Ok(None);
+        }
+        slot.last_uid = uid_str.clone();
+        if self.in_cooldown(&uid_str) {
+            debug!("Card {} re-tapped within rescan_cooldown, ignoring", uid_str);
+            return Ok(None);
+        }
+        info!("Card {} read on reader {}", uid_str, slot.label);
+        let guest_tag = card_guest_tag(card, &uid_str);
+        if guest_tag != uid_str {
+            info!("Card {} carries guest tag {}", uid_str, guest_tag);
+        }
+
+        // With lookup_workers the lookup runs on a worker, so a tap on another reader is
+        // not held up by this one; the worker gives the reader feedback itself
+        if config().lookup_workers > 1 {
+            self.dispatch(slot, guest_tag);
+            return Ok(None);
+        }
+
+        match self.lookup.run(&slot.label, &guest_tag) {
+            Err(AppError::TokenExpired) => {
+                // Forget the UID so the card is looked up again once the token is reloaded
+                slot.last_uid.clear();
+                self.recent_scans.remove(&uid_str);
+                Err(AppError::TokenExpired)
+            }
+            result => result.map(Some),
+        }
+    }
+}
+
+// "Last scan: 340 ms OK" on the status line, for the latest lookup
+fn show_last_scan(ui_handle: &Weak<AppWindow>, elapsed: Duration, result: &Result<ScanOutcome, AppError>) {
+    let outcome = match result {
+        Ok(outcome) => recent::outcome_label(*outcome),
+        Err(AppError::TokenExpired) => "token",
+        Err(_) => "error",
+    };
+    let text = trf("last_scan", "Last scan: {} ms {}", &[&elapsed.as_millis(), &outcome.to_uppercase()]);
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            ui.set_last_scan(SharedString::from(text));
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+// What a guests lookup found for a card
+#[derive(Debug)]
+enum GuestMatch {
+    One(Guest),
+    // Left to the operator; see show_guest_choice
+    Several(Vec<Guest>),
+    NotRegistered,
+}
+
+// An empty list means the same as a 404: the card is not registered. Other errors are
+// left to the caller.
+fn match_guests(response: Result<GuestsPostResponse, AppError>) -> Result<GuestMatch, AppError> {
+    match response {
+        Ok(resp) if resp.guests.len() > 1 => Ok(GuestMatch::Several(resp.guests)),
+        Ok(resp) => Ok(resp.guests.into_iter().next().map_or(GuestMatch::NotRegistered, GuestMatch::One)),
+        Err(AppError::GuestNotFound) => Ok(GuestMatch::NotRegistered),
+        Err(e) => Err(e),
+    }
+}
+
+// The server found this guest by the scanned UID, so the UID stands in for a missing tag
+fn guest_tag_or_uid(tag: Option<String>, uid_str: &str) -> String {
+    match tag.filter(|tag| !tag.trim().is_empty()) {
+        Some(tag) => tag,
+        None => {
+            warn!("Guest for card {} has no tag, using the scanned UID", uid_str);
+            uid_str.to_string()
+        }
+    }
+}
+
+// Guest lookup state shared by the scanner thread and the lookup workers
+struct Lookup {
+    api: Arc<dyn ApiClient>,
+    idle: Arc<IdleTracker>,
+    metrics: Arc<Metrics>,
+    ui_handle: Weak<AppWindow>,
+    guest_cache: Mutex<GuestCache>,
+}
+
+impl Lookup {
+    // Resolve a UID to a guest and show them on screen, then report how long it took
+    // on the operator's status line. Shared by the reader path and --simulate.
+    fn run(&self, reader: &str, uid_str: &str) -> Result<ScanOutcome, AppError> {
+        let started = Instant::now();
+        let result = self.resolve(reader, uid_str);
+        show_last_scan(&self.ui_handle, started.elapsed(), &result);
+        result
+    }
+
+    // Look the guest up (cache first, then the API) and show the result
+    fn resolve(&self, reader: &str, uid_str: &str) -> Result<ScanOutcome, AppError> {
+        let ui_handle = &self.ui_handle;
+        self.idle.touch();
+        self.metrics.record_scan();
+        let generation = SCAN_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+        // Taken out first so the cache is not locked during the API call
+        let cached = self.guest_cache.lock().unwrap().get(uid_str);
+        let guest = match cached {
+            Some(guest) => {
+                debug!("Guest cache hit for {}", uid_str);
+                guest
+            }
+            None => {
+                let response = self.api.get_guests(uid_str);
+                self.metrics.record_lookup(response.is_ok());
+                let found = match match_guests(response) {
+                    Ok(found) => found,
+                    Err(AppError::TokenExpired) => {
+                        audit::scan(reader, uid_str, None, "token_expired");
+                        return Err(AppError::TokenExpired);
+                    }
+                    // Answers came too slowly to keep the guest waiting
+                    Err(e @ AppError::DeadlineExceeded(_)) => {
+                        audit::scan(reader, uid_str, None, "timeout");
+                        warn!("Lookup for {} abandoned: {}", uid_str, e);
+                        show_error(ui_handle, &tr("network_too_slow", "Network too slow, please tap again"));
+                        recent::record(ui_handle, uid_str, ScanOutcome::NetworkError);
+                        return Ok(ScanOutcome::NetworkError);
+                    }
+                    // Nothing answered (and the roster, if any, did not know the card)
+                    Err(e @ AppError::Http(_)) => {
+                        audit::scan(reader, uid_str, None, "network_error");
+                        warn!("Lookup for {} failed, API unreachable: {}", uid_str, e);
+                        show_network_error(ui_handle);
+                        recent::record(ui_handle, uid_str, ScanOutcome::NetworkError);
+                        return Ok(ScanOutcome::NetworkError);
+                    }
+                    Err(e) => {
+                        audit::scan(reader, uid_str, None, &format!("error: {}", e));
+                        show_error(ui_handle, &trf("fetch_guests_failed", "Failed to fetch guests: {}", &[&e]));
+                        recent::record(ui_handle, uid_str, ScanOutcome::Error);
+                        return Ok(ScanOutcome::Error);
+                    }
+                };
+                debug!("Guests found: {:?}", found);
+
+                let guest = match found {
+                    GuestMatch::One(guest) => guest,
+                    GuestMatch::Several(guests) => {
+                        info!("Card {} matches {} guests, asking the operator", uid_str, guests.len());
+                        audit::scan(reader, uid_str, None, "multiple_guests");
+                        show_guest_choice(ui_handle, guests, generation);
+                        recent::record(ui_handle, uid_str, ScanOutcome::Success);
+                        return Ok(ScanOutcome::Success);
+                    }
+                    GuestMatch::NotRegistered => return Ok(self.not_registered(reader, uid_str)),
+                };
+                self.guest_cache.lock().unwrap().insert(uid_str.to_string(), guest.clone());
+                guest
+            }
+        };
+
+        let username = guest.name;
+        let tag = guest_tag_or_uid(guest.tag, uid_str);
+        let mut outcome = ScanOutcome::Error;
+        if tag.is_empty() {
+            audit::scan(reader, uid_str, Some(&username), "missing_tag");
+            show_error(ui_handle, &tr("guest_tag_missing", "Guest tag is missing in response"));
+        } else if username.trim().is_empty() {
+            // The tag resolved, so the guest can still play and be scored; the
+            // operator is only warned that the record needs fixing at the desk.
+            audit::scan(reader, uid_str, None, "missing_name");
+            warn!("Guest with tag {} has no name on file", tag);
+            outcome = ScanOutcome::Success;
+        } else {
+            audit::scan(reader, uid_str, Some(&username), "ok");
+            info!("Guest: {}, Tag: {}", username, tag);
+            outcome = ScanOutcome::Success;
+        }
+
+        recent::record(ui_handle, if username.trim().is_empty() { uid_str } else { &username }, outcome);
+
+        let weak = ui_handle.clone();
+        let success = outcome == ScanOutcome::Success;
+        let uid = uid_str.to_string();
+        slint::invoke_from_event_loop(move || {
+            if !is_latest_scan(generation) {
+                debug!("Discarding lookup result for {}, a newer card was scanned", uid);
+                return;
+            }
+            if let Some(ui) = weak.upgrade() {
+                present_guest(&ui, SharedString::from(username), SharedString::from(tag), success);
+            }
+        }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+
+        Ok(outcome)
+    }
+
+    // A card the API does not know, whether it answered 404 or an empty guests list
+    fn not_registered(&self, reader: &str, uid_str: &str) -> ScanOutcome {
+        audit::scan(reader, uid_str, None, "not_found");
+        info!("Card {} is not registered", uid_str);
+        show_unknown_card(&self.ui_handle);
+        recent::record(&self.ui_handle, uid_str, ScanOutcome::NotRegistered);
+        ScanOutcome::NotRegistered
+    }
+}
+
+// Show a resolved guest: in team mode they join the roster, otherwise they replace
+// the current guest on the welcome screen. Must run on the UI thread.
+pub fn present_guest(ui: &AppWindow, username: SharedString, tag: SharedString, success: bool) {
+    let notice = if success && username.trim().is_empty() {
+        SharedString::from(tr("guest_name_missing", "Guest has no name on file"))
+    } else {
+        SharedString::new()
+    };
+    // In team mode scans join the roster instead of replacing the current guest
+    if ui.get_team_mode() {
+        if success && team::add_member(ui, username, tag) {
+            ui.set_error_message(notice);
+        }
+        return;
+    }
+    // With toggle_mode, the guest on screen tapping again is checking out
+    if success && config().toggle_mode && is_displayed(ui, &tag) {
+        info!("Guest {} tapped out", tag);
+        ui.set_error_message(SharedString::new());
+        ui.set_current_screen(SharedString::from("goodbye"));
+        return_to_idle_later(ui, "goodbye");
+        return;
+    }
+    ui.set_user_name(username);
+    ui.set_current_screen(SharedString::from("welcome"));
+    ui.set_card_uid(tag);
+    if success {
+        ui.set_error_message(notice);
+        // One-tap station: score the checkpoint's points right away, nothing to enter.
+        // The callback only reads the screen; the submission itself runs on a worker.
+        if config().use_checkpoint_score
+            && let Some(trivia) = config().first_trivia()
+        {
+            ui.set_trivia_name(SharedString::from(trivia));
+            ui.invoke_submit_score(SharedString::new());
+        }
+    }
+}
+
+// True if the guest with `tag` is the one currently on the welcome, trivia or
+// confirmation screen
+fn is_displayed(ui: &AppWindow, tag: &str) -> bool {
+    let screen = ui.get_current_screen();
+    matches!(screen.as_str(), "welcome" | "trivia1" | "trivia2" | "score_confirmed")
+        && !tag.is_empty()
+        && ui.get_card_uid() == tag
+}
+
+// Guests the operator can pick from; one without a tag could not be scored
+fn guest_choices(guests: Vec<Guest>) -> Vec<GuestChoice> {
+    guests
+        .into_iter()
+        .filter_map(|guest| {
+            let tag = guest.tag.filter(|tag| !tag.is_empty())?;
+            Some(GuestChoice {
+                name: SharedString::from(guest.name),
+                tag: SharedString::from(tag),
+            })
+        })
+        .collect()
+}
+
+// A UID shared across events can match several guests; list the ones with a tag
+// and let the operator pick. The choice comes back through `guest_chosen`.
+fn show_guest_choice(ui_handle: &Weak<AppWindow>, guests: Vec<Guest>, generation: u64) {
+    let choices = guest_choices(guests);
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if !is_latest_scan(generation) {
+            return;
+        }
+        if let Some(ui) = weak.upgrade() {
+            ui.set_guest_choices(ModelRc::new(VecModel::from(choices)));
+            ui.set_current_screen(SharedString::from("guest_pick"));
+            ui.set_error_message(SharedString::new());
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockApi, Reply};
+
+    #[test]
+    fn lost_reader_is_dropped_and_the_others_kept() {
+        let mut readers = vec![
+            ReaderSlot::new(c"ACS ACR122U 00 00".to_owned()),
+            ReaderSlot::new(c"ACS ACR122U 01 00".to_owned()),
+        ];
+        readers[0].lost = true;
+        assert!(drop_lost_readers(&mut readers));
+        assert!(!drop_lost_readers(&mut readers));
+        let labels: Vec<&str> = readers.iter().map(|slot| slot.label.as_str()).collect();
+        assert_eq!(labels, ["ACS ACR122U 01 00"]);
+
+        let states = unaware_states(&readers, Some(State::UNAWARE));
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[1].name(), pcsc::PNP_NOTIFICATION());
+        assert_eq!(unaware_states(&readers, None).len(), 1);
+    }
+
+    #[test]
+    fn two_guests_are_left_to_the_operator() {
+        let api = MockApi::new().with_guests(
+            "04A1B2C3",
+            Reply::Body(vec![mock::guest("Ana", Some("04A1B2C3-2024")), mock::guest("Ana B.", Some("04A1B2C3-2025"))]),
+        );
+        let guests = match match_guests(api.get_guests("04A1B2C3")) {
+            Ok(GuestMatch::Several(guests)) => guests,
+            other => panic!("expected Several, got {:?}", other),
+        };
+        let choices: Vec<(String, String)> = guest_choices(guests)
+            .into_iter()
+            .map(|choice| (choice.name.to_string(), choice.tag.to_string()))
+            .collect();
+        assert_eq!(
+            choices,
+            [("Ana".to_string(), "04A1B2C3-2024".to_string()), ("Ana B.".to_string(), "04A1B2C3-2025".to_string())]
+        );
+    }
+
+    #[test]
+    fn one_guest_is_picked_automatically() {
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Body(vec![mock::guest("Ana", Some("04A1B2C3"))]));
+        assert!(matches!(match_guests(api.get_guests("04A1B2C3")), Ok(GuestMatch::One(guest)) if guest.name == "Ana"));
+    }
+
+    #[test]
+    fn guests_without_a_tag_are_not_offered() {
+        let choices = guest_choices(vec![mock::guest("Ana", Some("04A1B2C3")), mock::guest("Sin tag", None)]);
+        assert_eq!(choices.len(), 1);
+    }
+
+    #[test]
+    fn guest_with_null_tag_falls_back_to_the_scanned_uid() {
+        let guest: Guest = serde_json::from_str(r#"{"name": "Ana", "tag": null}"#).unwrap();
+        assert_eq!(guest_tag_or_uid(guest.tag, "04A1B2C3"), "04A1B2C3");
+
+        let guest: Guest = serde_json::from_str(r#"{"name": "Ana", "tag": "  "}"#).unwrap();
+        assert_eq!(guest_tag_or_uid(guest.tag, "04A1B2C3"), "04A1B2C3");
+
+        let guest: Guest = serde_json::from_str(r#"{"name": "Ana", "tag": "BADGE-17"}"#).unwrap();
+        assert_eq!(guest_tag_or_uid(guest.tag, "04A1B2C3"), "BADGE-17");
+    }
+
+    #[test]
+    fn empty_guests_list_is_not_registered() {
+        let resp: GuestsPostResponse = serde_json::from_str(r#"{"guests": []}"#).unwrap();
+        assert!(matches!(match_guests(crate::api::guests_result(Ok(resp))), Ok(GuestMatch::NotRegistered)));
+        // Same screen whether the empty list reaches the lookup or the API maps it first
+        assert!(matches!(match_guests(Ok(GuestsPostResponse { guests: Vec::new() })), Ok(GuestMatch::NotRegistered)));
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Status(404));
+        assert!(matches!(match_guests(api.get_guests("04A1B2C3")), Ok(GuestMatch::NotRegistered)));
+    }
+
+    #[test]
+    fn lookup_failures_are_not_mistaken_for_unknown_cards() {
+        let api = MockApi::new().with_guests("04A1B2C3", Reply::Offline);
+        assert!(matches!(match_guests(api.get_guests("04A1B2C3")), Err(AppError::DeadlineExceeded(_))));
+    }
+
+    const LENGTHS: &[usize] = &[4, 7, 10];
+
+    fn response(uid: &[u8], status: [u8; 2]) -> Vec<u8> {
+        [uid, &status].concat()
+    }
+
+    #[test]
+    fn uid_response_is_checked_and_formatted() {
+        let four = [0x04, 0xA1, 0xB2, 0xC3];
+        let seven = [0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+        let ten = [0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99];
+        assert_eq!(parse_uid_response(&response(&four, [0x90, 0x00]), LENGTHS, UidFormat::UpperNoSep).unwrap(), "04A1B2C3");
+        assert_eq!(parse_uid_response(&response(&seven, [0x90, 0x00]), LENGTHS, UidFormat::UpperNoSep).unwrap(), "04112233445566");
+        assert_eq!(parse_uid_response(&response(&ten, [0x90, 0x00]), LENGTHS, UidFormat::UpperNoSep).unwrap(), "04112233445566778899");
+        assert_eq!(parse_uid_response(&response(&four, [0x90, 0x00]), LENGTHS, UidFormat::LowerColon).unwrap(), "04:a1:b2:c3");
+    }
+
+    #[test]
+    fn uid_response_with_a_bad_trailer_is_a_read_error() {
+        match parse_uid_response(&response(&[0x04, 0xA1, 0xB2, 0xC3], [0x63, 0x00]), LENGTHS, UidFormat::UpperNoSep) {
+            Err(AppError::CardRead(message)) => assert!(message.contains("63 00"), "{}", message),
+            other => panic!("expected CardRead, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uid_response_too_short_is_a_read_error() {
+        assert!(matches!(parse_uid_response(&[0x90], LENGTHS, UidFormat::UpperNoSep), Err(AppError::CardRead(_))));
+        assert!(matches!(parse_uid_response(&[], LENGTHS, UidFormat::UpperNoSep), Err(AppError::CardRead(_))));
+        // A bare status word carries no UID at all
+        assert!(matches!(parse_uid_response(&[0x90, 0x00], &[], UidFormat::UpperNoSep), Err(AppError::UidLength(0))));
+    }
+
+    #[test]
+    fn uid_of_a_length_not_allowed_is_rejected() {
+        let eight = response(&[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77], [0x90, 0x00]);
+        assert!(matches!(parse_uid_response(&eight, LENGTHS, UidFormat::UpperNoSep), Err(AppError::UidLength(8))));
+        // No lengths listed means accept_any_uid_length
+        assert_eq!(parse_uid_response(&eight, &[], UidFormat::UpperNoSep).unwrap(), "0411223344556677");
+    }
+}
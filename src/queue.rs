@@ -0,0 +1,268 @@
+use log::{error, info, warn};
+use slint::Weak;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::api::{self, ApiClient, LoadScorePostPayload};
+use crate::audit;
+use crate::hook;
+use crate::config::config;
+use crate::{score_outcome, AppError, AppWindow};
+
+// Durable queue of load_score submissions that could not reach the server.
+// Entries are persisted to a JSON file so they survive restarts.
+pub struct ScoreQueue {
+    path: PathBuf,
+    entries: Mutex<Vec<LoadScorePostPayload>>,
+    // Idempotency keys the server has already answered definitively. If removing such
+    // an entry could not be persisted, it is dropped next round instead of resent.
+    settled: Mutex<HashSet<String>>,
+    // Set by push_batched until the drainer picks it up
+    batch_ready: AtomicBool,
+}
+
+impl ScoreQueue {
+    // Open the queue file, starting empty if it does not exist yet
+    pub fn open(path: &Path) -> Result<ScoreQueue, AppError> {
+        let mut entries: Vec<LoadScorePostPayload> = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(AppError::Config(format!("Failed to read {}: {}", path.display(), e)));
+            }
+        };
+        // Entries queued before idempotency keys existed get one now, kept from here on
+        for entry in entries.iter_mut().filter(|entry| entry.idempotency_key.is_empty()) {
+            entry.idempotency_key = api::idempotency_key(&api::new_submission_id(), &entry.guest_tag);
+        }
+        for entry in &entries {
+            audit::mark_pending(entry.checkpoint_id, &entry.guest_tag);
+        }
+        let queue = ScoreQueue {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            settled: Mutex::new(HashSet::new()),
+            batch_ready: AtomicBool::new(false),
+        };
+        info!("Score queue {} has {} pending entries", path.display(), queue.pending());
+        Ok(queue)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    // Copy of the waiting entries, oldest first
+    pub fn entries(&self) -> Vec<LoadScorePostPayload> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    // Add a failed submission. The token is not stored: the current one is used when draining.
+    pub fn push(&self, mut payload: LoadScorePostPayload) -> Result<(), AppError> {
+        payload.access_token.clear();
+        let mut entries = self.entries.lock().unwrap();
+        let (checkpoint_id, guest_tag) = (payload.checkpoint_id, payload.guest_tag.clone());
+        entries.push(payload);
+        self.persist(&entries)?;
+        audit::mark_pending(checkpoint_id, &guest_tag);
+        Ok(())
+    }
+
+    // Write to a temp file and rename so a crash never leaves a half-written queue
+    fn persist(&self, entries: &[LoadScorePostPayload]) -> Result<(), AppError> {
+        let tmp_path = self.path.with_extension("tmp");
+        let json = serde_json::to_string_pretty(entries)?;
+        std::fs::write(&tmp_path, json)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .map_err(|e| AppError::Config(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+
+    // Re-post queued entries in order, queue_batch_size at a time through
+    // ApiClient::load_scores, removing each one the server accepts or rejects outright.
+    // Stops at the first network failure so the remaining entries wait for the next round.
+    pub fn drain(&self, api: &dyn ApiClient) -> Result<(), AppError> {
+        loop {
+            let batch: Vec<LoadScorePostPayload> = {
+                let mut entries = self.entries.lock().unwrap();
+                let settled = self.settled.lock().unwrap();
+                let before = entries.len();
+                entries.retain(|entry| !settled.contains(&entry.idempotency_key));
+                if entries.len() < before {
+                    info!("{} queued scores were already answered, not resending", before - entries.len());
+                    self.persist(&entries)?;
+                }
+                entries.iter().take(config().queue_batch_size).cloned().collect()
+            };
+            if batch.is_empty() {
+                return Ok(());
+            }
+
+            let mut done = 0;
+            let mut stopped = None;
+            for (next, result) in batch.iter().zip(api.load_scores(&batch)) {
+                if !matches!(result, Err(AppError::NotSent)) {
+                    audit::score(&next.guest_tag, next.checkpoint_id, &next.score, &score_outcome(&result));
+                }
+                match result {
+                    Ok(resp) => {
+                        info!("Queued score for {} synced: {:?}", next.guest_tag, resp);
+                        if !resp.already_loaded() {
+                            hook::on_success(hook::ScoreEvent::from(next));
+                        }
+                    }
+                    // The server rejected the entry itself; retrying will never succeed.
+                    // A 429 is only the server asking to slow down and is kept.
+                    Err(e @ AppError::ApiError { status, .. }) if (400..500).contains(&status) && !crate::is_queueable(&e) => {
+                        warn!("Dropping queued score for {}: {}", next.guest_tag, e);
+                    }
+                    Err(e) => {
+                        stopped = Some(e);
+                        break;
+                    }
+                }
+                self.settled.lock().unwrap().insert(next.idempotency_key.clone());
+                done += 1;
+            }
+
+            // New entries are only ever appended, so the answered ones are still the first `done`
+            let mut entries = self.entries.lock().unwrap();
+            entries.drain(..done);
+            self.persist(&entries)?;
+            if let Some(e) = stopped {
+                return Err(e);
+            }
+        }
+    }
+
+    // Queue a score given in batching mode and have the drainer send it after
+    // score_batch_window, along with any other scores given meanwhile
+    pub fn push_batched(&self, payload: LoadScorePostPayload) -> Result<(), AppError> {
+        self.push(payload)?;
+        self.batch_ready.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // Push the pending count to the UI
+    pub fn publish_pending(&self, ui_handle: &Weak<AppWindow>) {
+        let pending = self.pending() as i32;
+        let weak = ui_handle.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(ui) = weak.upgrade() {
+                ui.set_pending_scores(pending);
+            }
+        }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+    }
+}
+
+// Background thread that periodically retries queued submissions until `shutdown` is set.
+// A drain in progress is allowed to finish so the queue file matches what the server has.
+pub fn spawn_drainer(
+    queue: Arc<ScoreQueue>,
+    api: Arc<dyn ApiClient>,
+    ui_handle: Weak<AppWindow>,
+    shutdown: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::SeqCst) {
+            if queue.pending() > 0 {
+                if let Err(e) = queue.drain(api.as_ref()) {
+                    warn!("Score queue drain stopped: {}", e);
+                }
+                queue.publish_pending(&ui_handle);
+            }
+
+            // Sleep in short steps so shutdown is not held up by the retry interval. A
+            // batched score brings the next round forward to score_batch_window from now.
+            let mut next_round = Instant::now() + config().queue_retry_interval;
+            while Instant::now() < next_round && !shutdown.load(Ordering::SeqCst) {
+                if queue.batch_ready.swap(false, Ordering::SeqCst) {
+                    next_round = next_round.min(Instant::now() + config().score_batch_window);
+                }
+                thread::sleep(next_round.saturating_duration_since(Instant::now()).min(Duration::from_millis(200)));
+            }
+        }
+        info!("Score queue drainer stopped");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockApi, Reply};
+
+    // Queue in a fresh temp file, removed again when the test ends
+    struct TempQueue {
+        queue: ScoreQueue,
+        path: PathBuf,
+    }
+
+    impl TempQueue {
+        fn new(name: &str, tags: &[&str]) -> TempQueue {
+            let path = std::env::temp_dir().join(format!("rpiui-queue-{}-{}.json", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            let queue = ScoreQueue::open(&path).unwrap();
+            for tag in tags {
+                queue
+                    .push(LoadScorePostPayload {
+                        access_token: String::new(),
+                        checkpoint_id: 9001,
+                        guest_tag: tag.to_string(),
+                        score: "10".to_string(),
+                        idempotency_key: api::idempotency_key(name, tag),
+                        guest_name: format!("Guest {}", tag),
+                        checkpoint_name: "Trivia".to_string(),
+                    })
+                    .unwrap();
+            }
+            TempQueue { queue, path }
+        }
+
+        fn tags(&self) -> Vec<String> {
+            self.queue.entries().into_iter().map(|entry| entry.guest_tag).collect()
+        }
+    }
+
+    impl Drop for TempQueue {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn rate_limited_entries_are_kept_for_the_next_round() {
+        let temp = TempQueue::new("429", &["07000001", "07000002"]);
+        let api = MockApi::new().with_scores("07000001", vec![Reply::Status(429)]);
+        assert!(matches!(temp.queue.drain(&api), Err(AppError::ApiError { status: 429, .. })));
+        assert_eq!(temp.tags(), ["07000001", "07000002"]);
+        // The entry after the 429 was not sent
+        assert_eq!(api.sent_tags(), ["07000001"]);
+
+        // Once the server accepts again the queue empties
+        temp.queue.drain(&api).unwrap();
+        assert_eq!(temp.queue.pending(), 0);
+    }
+
+    #[test]
+    fn rejected_entries_are_dropped() {
+        let temp = TempQueue::new("422", &["07000003", "07000004"]);
+        let api = MockApi::new().with_scores("07000003", vec![Reply::Status(422)]);
+        temp.queue.drain(&api).unwrap();
+        assert_eq!(temp.queue.pending(), 0);
+        assert_eq!(api.sent_tags(), ["07000003", "07000004"]);
+    }
+
+    #[test]
+    fn network_failures_keep_the_entry_and_the_rest() {
+        let temp = TempQueue::new("offline", &["07000005", "07000006", "07000007"]);
+        let api = MockApi::new().with_scores("07000006", vec![Reply::Offline]);
+        assert!(matches!(temp.queue.drain(&api), Err(AppError::DeadlineExceeded(_))));
+        assert_eq!(temp.tags(), ["07000006", "07000007"]);
+        // Survives a restart
+        let reopened = ScoreQueue::open(&temp.path).unwrap();
+        assert_eq!(reopened.pending(), 2);
+    }
+}
@@ -0,0 +1,39 @@
+use log::error;
+use slint::{Model, ModelRc, SharedString, VecModel, Weak};
+
+use crate::config::config;
+use crate::nfc::ScanOutcome;
+use crate::{AppWindow, RecentScan};
+
+// Add a scan to the recent-scans panel, newest first. The list is capped at
+// recent_scans_size, dropping the oldest entry. `label` is the guest's name, or the
+// UID when no guest was found.
+pub fn record(ui_handle: &Weak<AppWindow>, label: &str, outcome: ScanOutcome) {
+    let size = config().recent_scans_size;
+    if size == 0 {
+        return;
+    }
+    let entry = RecentScan {
+        name: SharedString::from(label),
+        time: SharedString::from(chrono::Local::now().format("%H:%M:%S").to_string()),
+        outcome: SharedString::from(outcome_label(outcome)),
+    };
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            let mut scans: Vec<RecentScan> = ui.get_recent_scans().iter().collect();
+            scans.insert(0, entry);
+            scans.truncate(size);
+            ui.set_recent_scans(ModelRc::new(VecModel::from(scans)));
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+pub fn outcome_label(outcome: ScanOutcome) -> &'static str {
+    match outcome {
+        ScanOutcome::Success => "ok",
+        ScanOutcome::Error => "error",
+        ScanOutcome::NotRegistered => "unknown",
+        ScanOutcome::NetworkError => "offline",
+    }
+}
@@ -0,0 +1,80 @@
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::api::{Guest, GuestsPostResponse};
+use crate::AppError;
+
+// Local copy of the guest list in SQLite, so cards still resolve when the venue has
+// no connectivity. Enabled by setting roster_path in config.toml. Schema:
+//
+//   guests(tag TEXT PRIMARY KEY,   -- guest_tag as the API returns it
+//          name TEXT NOT NULL,
+//          data TEXT NOT NULL,     -- the full guest object as JSON
+//          synced_at TEXT NOT NULL)
+//
+// It is seeded with `rpiui --sync-roster guests.json`, where guests.json is a saved
+// response of the guests endpoint ({"guests": [...]}), and every online lookup
+// is written through so the roster keeps itself current.
+pub struct Roster {
+    conn: Mutex<Connection>,
+}
+
+impl Roster {
+    pub fn open(path: &Path) -> Result<Roster, AppError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS guests (
+                tag TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                data TEXT NOT NULL,
+                synced_at TEXT NOT NULL
+            )",
+        )?;
+        info!("Guest roster: {}", path.display());
+        Ok(Roster { conn: Mutex::new(conn) })
+    }
+
+    // Insert or refresh guests; guests without a tag cannot be looked up and are skipped.
+    // Returns how many were stored.
+    pub fn upsert(&self, guests: &[Guest]) -> Result<usize, AppError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = chrono::Local::now().to_rfc3339();
+        let mut stored = 0;
+        for guest in guests {
+            let Some(tag) = guest.tag.as_deref().filter(|tag| !tag.is_empty()) else {
+                continue;
+            };
+            tx.execute(
+                "INSERT INTO guests (tag, name, data, synced_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(tag) DO UPDATE SET name = ?2, data = ?3, synced_at = ?4",
+                params![tag, guest.name, serde_json::to_string(guest)?, now],
+            )?;
+            stored += 1;
+        }
+        tx.commit()?;
+        Ok(stored)
+    }
+
+    // Look a card up without the network
+    pub fn resolve_guest_local(&self, uid: &str) -> Result<Option<Guest>, AppError> {
+        let conn = self.conn.lock().unwrap();
+        let data: Option<String> = conn
+            .query_row("SELECT data FROM guests WHERE tag = ?1", params![uid], |row| row.get(0))
+            .optional()?;
+        Ok(match data {
+            Some(data) => Some(serde_json::from_str(&data)?),
+            None => None,
+        })
+    }
+}
+
+// --sync-roster <file>: load a saved guests endpoint response into the roster
+pub fn sync_from_file(roster: &Roster, path: &Path) -> Result<usize, AppError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| AppError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+    let response: GuestsPostResponse = serde_json::from_str(&contents)?;
+    roster.upsert(&response.guests)
+}
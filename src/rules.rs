@@ -0,0 +1,26 @@
+use log::info;
+
+use crate::api::Checkpoint;
+use crate::audit;
+use crate::config::{config, ScoreRule};
+
+// Final score for `guest_tag` at `checkpoint`, from the score given at the kiosk (typed,
+// a preset, a trivia result or the checkpoint's own points) and the checkpoint's entry
+// in score_rules. A score that is not an integer is passed through for validation to reject.
+pub fn apply(checkpoint: &Checkpoint, guest_tag: &str, given: &str) -> String {
+    let Some(rule) = config().score_rules.get(&checkpoint.slug) else {
+        return given.to_string();
+    };
+    let Ok(base) = given.trim().parse::<i32>() else {
+        return given.to_string();
+    };
+    let score = match *rule {
+        ScoreRule::Fixed { points } => points,
+        ScoreRule::FirstTapBonus { bonus } if !audit::already_scored(checkpoint.id, guest_tag) => base.saturating_add(bonus),
+        ScoreRule::FirstTapBonus { .. } => base,
+    };
+    if score != base {
+        info!("Score rule {:?} for {}: {} -> {}", rule, checkpoint.slug, base, score);
+    }
+    score.to_string()
+}
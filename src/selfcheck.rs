@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use crate::api::{ApiClient, HttpApi};
+use crate::config;
+use crate::connectivity::Connectivity;
+use crate::metrics::Metrics;
+use crate::nfc;
+
+// --selfcheck: verify reader and API access without starting the UI.
+// Prints one line per check and returns true only if everything passed.
+pub fn run() -> bool {
+    let mut passed = true;
+
+    match nfc::list_matching_readers() {
+        Ok(readers) if !readers.is_empty() => {
+            for reader in &readers {
+                println!("[PASS] reader: {}", reader);
+            }
+        }
+        Ok(_) => {
+            println!("[FAIL] reader: no reader matching {:?}", config::config().reader_names);
+            passed = false;
+        }
+        Err(e) => {
+            println!("[FAIL] reader: {}", e);
+            passed = false;
+        }
+    }
+
+    // Every trivia's slug must resolve, or that trivia cannot be scored
+    let mut slugs: Vec<_> = config::config().checkpoint_slugs.iter().collect();
+    slugs.sort();
+    if slugs.is_empty() {
+        println!("[FAIL] api: checkpoint_slugs is empty");
+        passed = false;
+    }
+    for (trivia, slug) in slugs {
+        match check_api(slug) {
+            Ok(summary) => println!("[PASS] api {}: {}", trivia, summary),
+            Err(e) => {
+                println!("[FAIL] api {}: {}", trivia, e);
+                passed = false;
+            }
+        }
+    }
+
+    println!("{}", if passed { "Self-check passed" } else { "Self-check FAILED" });
+    passed
+}
+
+// Authenticated round trip: look up the checkpoint, then fetch its event visuals
+fn check_api(slug: &str) -> Result<String, crate::AppError> {
+    let token = config::load_access_token()?;
+    let api = HttpApi::new(token, Arc::new(Connectivity::default()), Arc::new(Metrics::default()))?;
+    let checkpoint = api.get_checkpoint(slug)?.checkpoint;
+    api.get_visual(checkpoint.event_id)?;
+    Ok(format!(
+        "checkpoint {:?} (id {}, event {}) reachable at {}",
+        checkpoint.name,
+        checkpoint.id,
+        checkpoint.event_id,
+        config::config().base_url
+    ))
+}
@@ -0,0 +1,77 @@
+use log::{info, warn};
+use rodio::Source;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use crate::config::config;
+use crate::nfc::ScanOutcome;
+
+// Scan outcomes waiting to be played. The audio thread owns the output stream, which
+// rodio does not let cross threads; unset when sound_enabled is off or nothing loaded.
+static PLAYER: OnceLock<Sender<ScanOutcome>> = OnceLock::new();
+
+// Load the configured sound files and start the audio thread, if sound_enabled is set.
+// The files are read and checked once here, so a scan never waits on the SD card.
+pub fn init() {
+    if !config().sound_enabled {
+        return;
+    }
+    let success = load("success", config().sound_success_path.as_deref());
+    let error = load("error", config().sound_error_path.as_deref());
+    if success.is_none() && error.is_none() {
+        warn!("sound_enabled is set but no sound file could be loaded, audio cues disabled");
+        return;
+    }
+
+    let (sender, receiver) = mpsc::channel::<ScanOutcome>();
+    thread::spawn(move || {
+        // Dropping the stream stops playback, so it lives as long as the thread
+        let (_stream, handle) = match rodio::OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("No audio output available, audio cues disabled: {}", e);
+                return;
+            }
+        };
+        for outcome in receiver {
+            let sound = if outcome == ScanOutcome::Success { &success } else { &error };
+            let Some(bytes) = sound else {
+                continue;
+            };
+            let result = rodio::Decoder::new(Cursor::new(bytes.clone()))
+                .map_err(|e| e.to_string())
+                .and_then(|source| handle.play_raw(source.convert_samples()).map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                warn!("Failed to play {:?} sound: {}", outcome, e);
+            }
+        }
+    });
+    let _ = PLAYER.set(sender);
+}
+
+// Queue the cue for a scan outcome; returns at once and does nothing with sound off
+pub fn play(outcome: ScanOutcome) {
+    if let Some(player) = PLAYER.get() {
+        let _ = player.send(outcome);
+    }
+}
+
+fn load(cue: &str, path: Option<&Path>) -> Option<Arc<[u8]>> {
+    let path = path?;
+    let bytes: Arc<[u8]> = match std::fs::read(path) {
+        Ok(bytes) => bytes.into(),
+        Err(e) => {
+            warn!("Could not read {} sound {}: {}", cue, path.display(), e);
+            return None;
+        }
+    };
+    if let Err(e) = rodio::Decoder::new(Cursor::new(bytes.clone())) {
+        warn!("Could not decode {} sound {}: {}", cue, path.display(), e);
+        return None;
+    }
+    info!("Loaded {} sound {}", cue, path.display());
+    Some(bytes)
+}
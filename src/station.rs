@@ -0,0 +1,72 @@
+use log::{info, warn};
+use std::collections::BTreeSet;
+
+use crate::api::ApiClient;
+use crate::config::{self, config};
+use crate::{AppError, StationChoice};
+use slint::SharedString;
+
+// Station assignment for single-image fleets: staff pick this unit's checkpoint on the
+// PIN-protected settings screen, and the slug is saved to station_path. While a station
+// is set, every trivia scores against it, overriding checkpoint_slugs.
+
+// Apply the station saved by an earlier run, if any. Called once at startup.
+pub fn apply_saved() {
+    if let Some(slug) = saved() {
+        info!("Station assigned from {}: {}", config().station_path.display(), slug);
+        apply(&slug);
+    }
+}
+
+// Slug saved in station_path, if a station has been assigned
+pub fn saved() -> Option<String> {
+    let path = &config().station_path;
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Some(contents.trim().to_string()).filter(|slug| !slug.is_empty()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("Cannot read station file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Save `slug` as this unit's station and start using it right away
+pub fn assign(slug: &str) -> Result<(), AppError> {
+    let path = &config().station_path;
+    std::fs::write(path, format!("{}\n", slug))
+        .map_err(|e| AppError::Config(format!("Failed to write {}: {}", path.display(), e)))?;
+    info!("Station set to {}, saved to {}", slug, path.display());
+    apply(slug);
+    Ok(())
+}
+
+fn apply(slug: &str) {
+    for trivia in config().checkpoint_slugs.keys() {
+        config::set_checkpoint_slug(trivia, slug);
+    }
+}
+
+// Checkpoints offered on the settings screen: station_slugs, or every slug in
+// checkpoint_slugs when that list is empty. Slugs the API does not know are left out.
+pub fn choices(api: &dyn ApiClient) -> Vec<StationChoice> {
+    let slugs: BTreeSet<String> = if config().station_slugs.is_empty() {
+        config().checkpoint_slugs.values().cloned().collect()
+    } else {
+        config().station_slugs.iter().cloned().collect()
+    };
+    slugs
+        .into_iter()
+        .filter_map(|slug| match api.get_checkpoint(&slug) {
+            Ok(resp) => Some(StationChoice {
+                name: SharedString::from(resp.checkpoint.name),
+                slug: SharedString::from(slug),
+                points: SharedString::from(resp.checkpoint.score.to_string()),
+            }),
+            Err(e) => {
+                warn!("Station {} left out of the list: {}", slug, e);
+                None
+            }
+        })
+        .collect()
+}
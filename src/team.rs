@@ -0,0 +1,197 @@
+use log::{error, info, warn};
+use slint::{Model, ModelRc, SharedString, VecModel, Weak};
+
+use crate::api::{self, ApiClient, Checkpoint, LoadScorePostResponse};
+use crate::config::config;
+use crate::hook;
+use crate::i18n::{tr, trf};
+use crate::queue::ScoreQueue;
+use crate::{is_queueable, post_multiple_guests_and_scores, queue_score, show_confirmation, show_error, AppWindow, TeamMember};
+
+// Enter team mode, seeding the roster with the guest currently on screen
+pub fn start(ui: &AppWindow) {
+    let mut members = Vec::new();
+    let tag = ui.get_card_uid();
+    if !tag.is_empty() {
+        members.push(TeamMember {
+            name: ui.get_user_name(),
+            tag,
+            status: SharedString::new(),
+        });
+    }
+    ui.set_team_members(ModelRc::new(VecModel::from(members)));
+    ui.set_team_mode(true);
+}
+
+// Add a scanned guest to the roster; a card scanned twice is only counted once.
+// Once max_batch_size members are in, further cards are refused so a stuck reader
+// cannot pile tags into one submission. Returns false if the card was refused.
+// Must run on the UI thread.
+pub fn add_member(ui: &AppWindow, name: SharedString, tag: SharedString) -> bool {
+    let mut members: Vec<TeamMember> = ui.get_team_members().iter().collect();
+    if members.iter().any(|m| m.tag == tag) {
+        return true;
+    }
+    let max = config().max_batch_size;
+    if members.len() >= max {
+        warn!("Team is full ({} members), ignoring {}", max, tag);
+        let msg = trf("team_full", "Team full ({} cards)", &[&max]);
+        ui.set_error_message(SharedString::from(trf("error_prefix", "Error: {}", &[&msg])));
+        return false;
+    }
+    info!("Added {} to the team", tag);
+    members.push(TeamMember {
+        name,
+        tag,
+        status: SharedString::new(),
+    });
+    ui.set_team_members(ModelRc::new(VecModel::from(members)));
+    true
+}
+
+// Leave team mode and forget the roster
+pub fn reset(ui: &AppWindow) {
+    ui.set_team_mode(false);
+    ui.set_team_members(ModelRc::new(VecModel::<TeamMember>::default()));
+}
+
+// (tag, name) of each member of the current roster, or None when not in team mode
+pub fn members(ui_handle: &Weak<AppWindow>) -> Option<Vec<(String, String)>> {
+    let ui = ui_handle.upgrade()?;
+    if !ui.get_team_mode() {
+        return None;
+    }
+    Some(ui.get_team_members().iter().map(|m| (m.tag.to_string(), m.name.to_string())).collect())
+}
+
+// Submit the given score for every team member, each after score_rules, and show each
+// member's outcome. Failures that can be retried later go to the offline queue.
+pub fn submit(
+    api: &dyn ApiClient,
+    score_queue: &ScoreQueue,
+    ui_handle: &Weak<AppWindow>,
+    members: &[(String, String)],
+    checkpoint: &Checkpoint,
+    score: &str,
+) {
+    if members.is_empty() {
+        show_error(ui_handle, &tr("team_empty", "No cards scanned for the team"));
+        return;
+    }
+
+    let tags: Vec<String> = members.iter().map(|(tag, _)| tag.clone()).collect();
+    // Results come back with trimmed tags
+    let name_of = |tag: &str| {
+        members.iter().find(|(t, _)| t.trim() == tag).map(|(_, name)| name.as_str()).unwrap_or_default()
+    };
+    let submission_id = api::new_submission_id();
+    let results = post_multiple_guests_and_scores(api, &tags, checkpoint, score, &submission_id);
+    let mut failed = 0;
+    let mut queued = 0;
+    // Each member's score after score_rules, which may differ from the one given
+    let scores: Vec<String> = results.iter().map(|(_, member_score, _)| member_score.clone()).collect();
+    let statuses: Vec<(String, &'static str)> = results
+        .into_iter()
+        .map(|(tag, member_score, result)| {
+            let status = match result {
+                Ok(resp) => {
+                    if !resp.already_loaded() {
+                        hook::on_success(hook::ScoreEvent {
+                            name: name_of(&tag).to_string(),
+                            tag: tag.clone(),
+                            checkpoint: checkpoint.name.clone(),
+                            checkpoint_id: checkpoint.id,
+                            score: member_score,
+                        });
+                    }
+                    member_status(&resp)
+                }
+                Err(e) if is_queueable(&e) => match queue_score(score_queue, checkpoint, name_of(&tag), &tag, &member_score, &submission_id) {
+                    Ok(()) => {
+                        queued += 1;
+                        "queued"
+                    }
+                    Err(qe) => {
+                        warn!("Failed to queue team score for {}: {}", tag, qe);
+                        failed += 1;
+                        "error"
+                    }
+                },
+                Err(e) => {
+                    warn!("Team score for {} rejected: {}", tag, e);
+                    failed += 1;
+                    "error"
+                }
+            };
+            (tag, status)
+        })
+        .collect();
+    info!("Team score submitted: {} guests, {} queued, {} failed", statuses.len(), queued, failed);
+
+    if queued > 0 {
+        score_queue.publish_pending(ui_handle);
+    }
+    let total = statuses.len();
+    set_statuses(ui_handle, statuses);
+
+    if failed > 0 {
+        show_error(ui_handle, &trf("team_failed", "{} of {} team scores failed", &[&failed, &total]));
+    } else {
+        show_confirmation(ui_handle, &confirmed_score(&scores, score), &checkpoint.name, false, None);
+    }
+}
+
+// The score shown on the confirmation screen: the one every member got, or the lowest
+// to highest when score_rules gave members different scores
+fn confirmed_score(scores: &[String], given: &str) -> String {
+    let mut points: Vec<i32> = scores.iter().filter_map(|score| score.trim().parse().ok()).collect();
+    points.sort_unstable();
+    match (points.first(), points.last()) {
+        (Some(low), Some(high)) if low != high => format!("{}–{}", low, high),
+        (Some(low), _) => low.to_string(),
+        _ => given.to_string(),
+    }
+}
+
+fn member_status(resp: &LoadScorePostResponse) -> &'static str {
+    if resp.already_loaded() {
+        "already"
+    } else {
+        "ok"
+    }
+}
+
+fn set_statuses(ui_handle: &Weak<AppWindow>, statuses: Vec<(String, &'static str)>) {
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade() {
+            let members: Vec<TeamMember> = ui
+                .get_team_members()
+                .iter()
+                .map(|mut m| {
+                    if let Some((_, status)) = statuses.iter().find(|(tag, _)| m.tag == tag.as_str()) {
+                        m.status = SharedString::from(*status);
+                    }
+                    m
+                })
+                .collect();
+            ui.set_team_members(ModelRc::new(VecModel::from(members)));
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(scores: &[&str]) -> Vec<String> {
+        scores.iter().map(|score| score.to_string()).collect()
+    }
+
+    #[test]
+    fn confirmed_score_is_the_members_score() {
+        assert_eq!(confirmed_score(&scores(&["15", "15"]), "10"), "15");
+        assert_eq!(confirmed_score(&scores(&["15", "10", "15"]), "10"), "10–15");
+        assert_eq!(confirmed_score(&[], "10"), "10");
+    }
+}
@@ -0,0 +1,83 @@
+use log::{info, warn};
+use slint::{Color, ComponentHandle, Image, SharedString, Timer, TimerMode};
+
+use crate::api::{ApiClient, VisualConfig};
+use crate::config::config;
+use crate::{AppWindow, Theme};
+
+// Fetch the event branding for the checkpoint and push it into the Theme global.
+// Any failure leaves the built-in theme in place. Returns the event's idle messages.
+pub fn apply_event_theme(ui: &AppWindow, api: &dyn ApiClient, slug: &str) -> Vec<String> {
+    let visual = match api.get_checkpoint(slug).and_then(|resp| api.get_visual(resp.checkpoint.event_id)) {
+        Ok(visual) => visual,
+        Err(e) => {
+            warn!("Could not load event visuals, using defaults: {}", e);
+            return Vec::new();
+        }
+    };
+    apply_visual(ui, &visual);
+    visual.idle_messages.unwrap_or_default()
+}
+
+// Fill the idle screen: idle_image, and idle_messages (or else `event_messages`) rotating
+// every idle_message_interval. The returned timer drives the rotation and must be kept alive.
+pub fn start_attract_loop(ui: &AppWindow, event_messages: Vec<String>) -> Option<Timer> {
+    let theme = ui.global::<Theme>();
+    if let Some(path) = &config().idle_image {
+        match Image::load_from_path(path) {
+            Ok(image) => {
+                theme.set_idle_image(image);
+                theme.set_has_idle_image(true);
+            }
+            Err(e) => warn!("Cannot load idle_image {}: {}", path.display(), e),
+        }
+    }
+
+    let messages = if config().idle_messages.is_empty() { event_messages } else { config().idle_messages.clone() };
+    let messages: Vec<SharedString> = messages
+        .iter()
+        .map(|message| message.trim())
+        .filter(|message| !message.is_empty())
+        .map(SharedString::from)
+        .collect();
+    let first = messages.first()?;
+    theme.set_idle_message(first.clone());
+    if messages.len() < 2 {
+        return None;
+    }
+
+    let weak = ui.as_weak();
+    let mut next = 1;
+    let timer = Timer::default();
+    timer.start(TimerMode::Repeated, config().idle_message_interval, move || {
+        if let Some(ui) = weak.upgrade() {
+            ui.global::<Theme>().set_idle_message(messages[next].clone());
+            next = (next + 1) % messages.len();
+        }
+    });
+    Some(timer)
+}
+
+fn apply_visual(ui: &AppWindow, visual: &VisualConfig) {
+    let theme = ui.global::<Theme>();
+    if let Some(title) = &visual.title {
+        info!("Event title: {}", title);
+        theme.set_event_title(SharedString::from(title.as_str()));
+    }
+    if let Some(hex) = &visual.primary_color {
+        match parse_hex_color(hex) {
+            Some(color) => theme.set_primary(color),
+            None => warn!("Ignoring invalid primary_color {:?}", hex),
+        }
+    }
+}
+
+// Parse "#RRGGBB" (leading '#' optional)
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::from_rgb_u8((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
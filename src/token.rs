@@ -0,0 +1,111 @@
+use log::{error, info, warn};
+use slint::{SharedString, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::api::ApiClient;
+use crate::config::{self, config};
+use crate::i18n::{tr, trf};
+use crate::{show_persistent_error, AppError, AppWindow};
+
+// Set while the API refuses our token. The scanner looks nothing up meanwhile, so a
+// long event does not hammer the API with requests that cannot succeed.
+static SCANS_PAUSED: AtomicBool = AtomicBool::new(false);
+
+// The API answered 401: raise the "token expired" banner and pause scans until resume
+pub fn expired(ui_handle: &Weak<AppWindow>) {
+    if !SCANS_PAUSED.swap(true, Ordering::SeqCst) {
+        warn!("Access token expired, pausing scans until it is reloaded");
+    }
+    show_persistent_error(ui_handle, &expired_message());
+}
+
+pub fn scans_paused() -> bool {
+    SCANS_PAUSED.load(Ordering::SeqCst)
+}
+
+// A new token is in use: take the banner down and let the scanner look cards up again
+pub fn resume(ui_handle: &Weak<AppWindow>) {
+    if SCANS_PAUSED.swap(false, Ordering::SeqCst) {
+        info!("Access token reloaded, resuming scans");
+    }
+    clear_token_expired(ui_handle);
+}
+
+// Re-read the token (--token, RPIUI_ACCESS_TOKEN or token.txt) into the API client and
+// resume. Every reload goes through here, whatever triggered it.
+pub fn reload(api: &dyn ApiClient, ui_handle: &Weak<AppWindow>) -> Result<(), AppError> {
+    let token = config::load_access_token()?;
+    api.set_access_token(token);
+    resume(ui_handle);
+    Ok(())
+}
+
+fn expired_message() -> String {
+    tr("token_expired", "Access token expired — reload token")
+}
+
+// Background thread that reloads token.txt whenever it is replaced, so an expired token
+// can be rotated mid-event without a restart. Checks the file's mtime every
+// token_check_interval. Scans paused by an expired token resume once the new token is in.
+pub fn spawn_watcher(api: Arc<dyn ApiClient>, ui_handle: Weak<AppWindow>) {
+    let interval = config().token_check_interval;
+    if interval.is_zero() {
+        info!("Token file watching disabled");
+        return;
+    }
+    // --token and the environment variable win over the file, so changes to it would be
+    // ignored. Scans paused by an expired token then resume through the control endpoint
+    // or the heartbeat.
+    if config().access_token.is_some() {
+        info!("Access token comes from --token, not watching {}", config::TOKEN_FILE);
+        return;
+    }
+    if std::env::var("RPIUI_ACCESS_TOKEN").is_ok_and(|token| !token.trim().is_empty()) {
+        info!("Access token comes from RPIUI_ACCESS_TOKEN, not watching {}", config::TOKEN_FILE);
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut last_modified = config::token_file_modified();
+        loop {
+            thread::sleep(interval);
+            let modified = config::token_file_modified();
+            if modified == last_modified || modified.is_none() {
+                continue;
+            }
+            last_modified = modified;
+            match reload(api.as_ref(), &ui_handle) {
+                Ok(()) => info!("{} changed, access token reloaded", config::TOKEN_FILE),
+                Err(e) => warn!("{} changed but could not be loaded: {}", config::TOKEN_FILE, e),
+            }
+        }
+    });
+}
+
+// Remove the "token expired" banner, leaving any other error in place
+fn clear_token_expired(ui_handle: &Weak<AppWindow>) {
+    let weak = ui_handle.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = weak.upgrade()
+            && ui.get_error_message() == trf("error_prefix", "Error: {}", &[&expired_message()]).as_str()
+        {
+            ui.set_error_message(SharedString::new());
+        }
+    }).unwrap_or_else(|e| error!("Event loop error: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_token_pauses_scans_until_resumed() {
+        let ui_handle = Weak::default();
+        expired(&ui_handle);
+        assert!(scans_paused());
+        resume(&ui_handle);
+        assert!(!scans_paused());
+    }
+}